@@ -0,0 +1,78 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+// The files which are written from scratch (the database when it is created, the tries and the
+// indexes) are written next to the old ones, e.g. name_trie.writing.bin, and renamed over them
+// when they are complete, so an interruption leaves the old file as it was
+pub fn temp_file(file: &str) -> String {
+    let path = Path::new(file);
+    let extension = match path.extension() {
+        Some(extension) => format!("writing.{}", extension.to_string_lossy()),
+        None => "writing".to_string(),
+    };
+
+    path.with_extension(extension).to_string_lossy().into_owned()
+}
+
+pub fn create(file: &str) -> io::Result<File> {
+    File::create(temp_file(file))
+}
+
+// Makes sure the temporary file is in the disk, without replacing the old one yet
+pub fn sync(mut writer: BufWriter<File>) -> io::Result<()> {
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+pub fn commit(file: &str, writer: BufWriter<File>) -> io::Result<()> {
+    sync(writer)?;
+    fs::rename(temp_file(file), file)
+}
+
+// Renames one of the files of a commit, or removes it when it doesn't have a new name
+fn rename_or_remove(from: &str, to: &str) -> io::Result<()> {
+    if to.is_empty() {
+        fs::remove_file(from)
+    } else {
+        fs::rename(from, to)
+    }
+}
+
+// Renames the (complete) files over the ones which depend on each other, like the database and
+// its index, and removes the ones which are paired with an empty name. The renames are listed in
+// `commit_file` before they are done, so the ones left by an interruption are done by `recover`,
+// and the files are never mixed from before and after
+pub fn replace_all(commit_file: &str, renames: &[(String, String)]) -> io::Result<()> {
+    let mut list = BufWriter::new(create(commit_file)?);
+    for (from, to) in renames {
+        writeln!(list, "{}\n{}", from, to)?;
+    }
+    commit(commit_file, list)?;
+
+    for (from, to) in renames {
+        rename_or_remove(from, to)?;
+    }
+    fs::remove_file(commit_file)
+}
+
+// Finishes the renames of an interrupted `replace_all`. Returns false when there weren't any
+pub fn recover(commit_file: &str) -> io::Result<bool> {
+    if !Path::new(commit_file).exists() {
+        return Ok(false);
+    }
+
+    let lines: Vec<String> = BufReader::new(File::open(commit_file)?)
+        .lines()
+        .collect::<io::Result<_>>()?;
+    for rename in lines.chunks_exact(2) {
+        if Path::new(&rename[0]).exists() {
+            rename_or_remove(&rename[0], &rename[1])?;
+        }
+    }
+
+    fs::remove_file(commit_file)?;
+    Ok(true)
+}
@@ -0,0 +1,109 @@
+use columns::Column;
+use std::{error::Error, fmt};
+
+pub const CATEGORY_NAMES: [&str; 5] = [
+    "Servidores",
+    "Militares",
+    "Aposentados",
+    "Pensionistas",
+    "Honorarios",
+];
+
+// Each one of the Remuneracao/Cadastro pairs published in the Servidores download
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+    Servidores,
+    Militares,
+    Aposentados,
+    Pensionistas,
+    Honorarios,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Servidores => CATEGORY_NAMES[0],
+            Category::Militares => CATEGORY_NAMES[1],
+            Category::Aposentados => CATEGORY_NAMES[2],
+            Category::Pensionistas => CATEGORY_NAMES[3],
+            Category::Honorarios => CATEGORY_NAMES[4],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Category, Box<dyn Error>> {
+        match name.to_lowercase().as_str() {
+            "servidores" | "servidor" => Ok(Category::Servidores),
+            "militares" | "militar" => Ok(Category::Militares),
+            "aposentados" | "aposentado" => Ok(Category::Aposentados),
+            "pensionistas" | "pensionista" => Ok(Category::Pensionistas),
+            "honorarios" | "honorários" | "jetons" => Ok(Category::Honorarios),
+            _ => Err(From::from(format!(
+                "Unknown category \"{}\", expected one of: {}",
+                name,
+                CATEGORY_NAMES.join(", ")
+            ))),
+        }
+    }
+
+    // The Portal names the files (and the zips they come in) after their category,
+    // e.g. 201807_Militares.zip or 201807_Honorarios(Jetons).csv
+    pub fn detect(file: &str) -> Category {
+        let file = file.to_lowercase();
+        if file.contains("militar") {
+            Category::Militares
+        } else if file.contains("aposentad") {
+            Category::Aposentados
+        } else if file.contains("pensionista") {
+            Category::Pensionistas
+        } else if file.contains("honorario") || file.contains("jeton") {
+            Category::Honorarios
+        } else {
+            Category::Servidores
+        }
+    }
+
+    // Columns without which a Remuneracao file of this category can't be loaded.
+    // The Honorarios file only has the value of the jeton, which is read as the gross remuneration
+    pub fn required_salary_columns(self) -> &'static [Column] {
+        match self {
+            Category::Honorarios => &[
+                Column::Id,
+                Column::Cpf,
+                Column::Nome,
+                Column::RemuneracaoBasicaBruta,
+            ],
+            _ => &[
+                Column::Id,
+                Column::Cpf,
+                Column::Nome,
+                Column::RemuneracaoBasicaBruta,
+                Column::RemuneracaoAposDeducoes,
+            ],
+        }
+    }
+
+    // Columns without which a Cadastro file of this category can't be loaded.
+    // Only the civil servants have every column, the other ones vary between the categories.
+    // The function and UORG of the links are never required, as not every layout has them
+    pub fn required_info_columns(self) -> &'static [Column] {
+        match self {
+            Category::Servidores => &[
+                Column::Id,
+                Column::DescricaoCargo,
+                Column::OrgaoExercicio,
+                Column::DataInicioAfastamento,
+                Column::DataTerminoAfastamento,
+                Column::JornadaTrabalho,
+                Column::DataIngressoCargo,
+                Column::DataIngressoOrgao,
+            ],
+            _ => &[Column::Id, Column::DescricaoCargo, Column::OrgaoExercicio],
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
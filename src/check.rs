@@ -0,0 +1,257 @@
+use crc32fast;
+use header::FileKind;
+use lookup_index;
+use parser;
+use record_view::MappedDatabase;
+use salary_index;
+use sorted_index;
+use std::{collections::HashSet, error::Error, path::Path};
+use trie;
+
+// Quantity of problems printed for each of the files
+const REPORTED_PROBLEMS: usize = 10;
+
+// The problems found in one of the files
+struct FileReport {
+    file: String,
+    problems: Vec<String>,
+}
+
+impl FileReport {
+    fn new(file: &str) -> FileReport {
+        FileReport {
+            file: file.to_string(),
+            problems: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, problem: String) {
+        self.problems.push(problem);
+    }
+
+    // Returns whether the file is fine
+    fn print(&self) -> bool {
+        if self.problems.is_empty() {
+            println!("{}: OK", self.file);
+            return true;
+        }
+
+        println!("{}: {} problem(s)", self.file, self.problems.len());
+        for problem in self.problems.iter().take(REPORTED_PROBLEMS) {
+            println!("    {}", problem);
+        }
+        if self.problems.len() > REPORTED_PROBLEMS {
+            println!("    ... and {} more", self.problems.len() - REPORTED_PROBLEMS);
+        }
+        false
+    }
+}
+
+// The header, the checksum, the index and every record, which must be inside of the file and
+// have valid UTF-8 text. Returns the quantity of deleted entries
+fn check_database(report: &mut FileReport, database: &MappedDatabase, database_file: &str) -> u64 {
+    if !database.index_len().is_multiple_of(8) {
+        report.add(format!(
+            "{} ends in the middle of an offset",
+            parser::index_file(database_file)
+        ));
+    }
+    let records_count = database.header.records_count;
+    let indexed_count = database.index_len() as u64 / 8;
+    if indexed_count != records_count {
+        report.add(format!(
+            "{} has {} entries, but the header says there are {} records",
+            parser::index_file(database_file),
+            indexed_count,
+            records_count
+        ));
+    }
+    if crc32fast::hash(database.body()) != database.header.checksum {
+        report.add("its checksum is wrong".to_string());
+    }
+
+    let mut deleted = 0;
+    for entry in 1..=records_count.min(indexed_count) as u32 {
+        match database.get(entry) {
+            Ok(Some(_)) => {}
+            Ok(None) => deleted += 1,
+            Err(err) => report.add(format!("entry {}: {}", entry, err)),
+        }
+    }
+
+    deleted
+}
+
+// The entries in a trie or an index must be records of the database, up to the quantity of
+// records it had when the file was generated
+fn check_entries<I: Iterator<Item = u32>>(
+    report: &mut FileReport,
+    entries: I,
+    first: u64,
+    last: u64,
+    records_count: u64,
+) {
+    let mut reported: HashSet<u32> = HashSet::new();
+    for entry in entries {
+        let entry_number = u64::from(entry);
+        if (entry_number < first || entry_number > last || entry_number > records_count)
+            && reported.insert(entry)
+        {
+            report.add(format!(
+                "it points to the entry {}, which isn't between {} and {}",
+                entry,
+                first,
+                last.min(records_count)
+            ));
+        }
+    }
+}
+
+fn check_trie(field: &trie::IndexedField, database: Option<&MappedDatabase>) -> Vec<FileReport> {
+    let trie_file = field.trie_path();
+    let mut report = FileReport::new(&trie_file);
+    let mut indexed_count: Option<u64> = None;
+
+    if !Path::new(&trie_file).exists() {
+        report.add("it doesn't exist, generate it with -t".to_string());
+    } else {
+        match trie::check_file(&trie_file) {
+            Ok((header, values)) => {
+                indexed_count = Some(header.records_count);
+                if let Some(database) = database {
+                    if header.records_count > database.header.records_count {
+                        report.add(format!(
+                            "it was generated from {} records, but the database has {}",
+                            header.records_count, database.header.records_count
+                        ));
+                    }
+                    check_entries(
+                        &mut report,
+                        values.into_iter(),
+                        1,
+                        header.records_count,
+                        database.header.records_count,
+                    );
+                }
+            }
+            Err(err) => report.add(err.to_string()),
+        }
+    }
+
+    // The workers inserted after the trie was generated must be in the memory trie, which only
+    // has them and the updated entries
+    let memory_trie_file = field.memory_trie_path();
+    let mut memory_report = FileReport::new(&memory_trie_file);
+    let mut memory_entries: HashSet<u32> = HashSet::new();
+    if Path::new(&memory_trie_file).exists() {
+        match trie::check_file(&memory_trie_file) {
+            Ok((_, values)) => {
+                if let Some(database) = database {
+                    check_entries(
+                        &mut memory_report,
+                        values.iter().cloned(),
+                        1,
+                        database.header.records_count,
+                        database.header.records_count,
+                    );
+                }
+                memory_entries.extend(values);
+            }
+            Err(err) => memory_report.add(err.to_string()),
+        }
+    }
+
+    if let (Some(database), Some(indexed_count)) = (database, indexed_count) {
+        for entry in indexed_count as u32 + 1..=database.header.records_count as u32 {
+            if let Ok(Some(view)) = database.get(entry) {
+                if !view.indexed_words(field.record_index).is_empty() && !memory_entries.contains(&entry) {
+                    memory_report.add(format!(
+                        "the entry {} was inserted after {} was generated, but isn't in it (generate the tries again with -t)",
+                        entry, trie_file
+                    ));
+                }
+            }
+        }
+    }
+
+    vec![report, memory_report]
+}
+
+fn check_sorted_index(index_file: &str, kind: FileKind, database: Option<&MappedDatabase>) -> FileReport {
+    let mut report = FileReport::new(index_file);
+    if !Path::new(index_file).exists() {
+        report.add("it doesn't exist, generate it with -t".to_string());
+        return report;
+    }
+
+    match sorted_index::check_file(index_file, kind) {
+        Ok((header, entries)) => {
+            if let Some(database) = database {
+                if header.records_count > database.header.records_count {
+                    report.add(format!(
+                        "it was generated from {} records, but the database has {}",
+                        header.records_count, database.header.records_count
+                    ));
+                }
+                check_entries(
+                    &mut report,
+                    entries.into_iter(),
+                    1,
+                    header.records_count,
+                    database.header.records_count,
+                );
+            }
+        }
+        Err(err) => report.add(err.to_string()),
+    }
+
+    report
+}
+
+// Checks the database, the tries and the indexes, printing the problems found in each of them.
+// The files which can't be opened, or are from another version, are reported like any other
+// problem, and the other files are still checked. Returns whether they are all fine
+pub fn verify_data_files() -> Result<bool, Box<dyn Error>> {
+    let database_file = parser::database_file();
+    if !Path::new(&database_file).exists() {
+        return Err(From::from(format!("The database {} doesn't exist", database_file)));
+    }
+
+    let mut report = FileReport::new(&database_file);
+    let database = match MappedDatabase::open(&database_file) {
+        Ok(database) => Some(database),
+        Err(err) => {
+            report.add(err.to_string());
+            None
+        }
+    };
+    let deleted = match database {
+        Some(ref database) => check_database(&mut report, database, &database_file),
+        None => 0,
+    };
+    let mut fine = report.print();
+    if let Some(ref database) = database {
+        println!("    {} entries, {} deleted", database.header.records_count, deleted);
+    }
+
+    let mut reports: Vec<FileReport> = Vec::new();
+    for field in trie::INDEXED_FIELDS.iter() {
+        reports.extend(check_trie(field, database.as_ref()));
+    }
+    for field in salary_index::SALARY_FIELDS.iter() {
+        reports.push(check_sorted_index(&field.index_path(), FileKind::SalaryIndex, database.as_ref()));
+    }
+    for field in lookup_index::LOOKUP_FIELDS.iter() {
+        reports.push(check_sorted_index(&field.index_path(), FileKind::LookupIndex, database.as_ref()));
+    }
+
+    for report in reports {
+        // The memory tries only exist after inserting workers
+        if !Path::new(&report.file).exists() && report.problems.is_empty() {
+            continue;
+        }
+        fine = report.print() && fine;
+    }
+
+    Ok(fine)
+}
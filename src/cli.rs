@@ -1,16 +1,78 @@
+use category::{self, Category};
+use check;
+use compact;
 use csv::ReaderBuilder;
+use data_dir;
+use diff;
+use encoding::Encoding;
+use filter::DateFilter;
+use header::{FileKind, Header};
+use journal;
+use lock::DataLock;
+use lookup_index::{self, LookupField};
+use migrate;
+use money;
 use parser;
+use period::{Period, PeriodRange};
 use prettytable::{format, Table};
 use record;
+use record_view::{MappedDatabase, RecordView};
+use rejects::{self, Rejects};
+use replaced;
+use salary_index::{self, SalaryField};
+use sorter;
+use source::CsvSource;
 use std::{
+    collections::HashSet,
     error,
-    fs::{self, OpenOptions},
+    fs,
     io::{self, Write},
+    path::Path,
     process, str, thread,
     time::Instant,
 };
 use trie;
 
+// In-memory tries holding the entries inserted after the tries were generated, one for each of
+// the trie::INDEXED_FIELDS. They are only loaded to be changed, under the exclusive lock
+struct MemoryTries {
+    tries: Vec<trie::Trie>,
+}
+
+impl MemoryTries {
+    fn load() -> MemoryTries {
+        let mut tries: Vec<trie::Trie> = Vec::new();
+        for field in trie::INDEXED_FIELDS.iter() {
+            tries.push(
+                if let Ok(new_trie) = trie::Trie::new_from_file(field.memory_trie_path()) {
+                    new_trie
+                } else {
+                    trie::Trie::new()
+                },
+            );
+        }
+
+        MemoryTries { tries }
+    }
+
+    fn add(&mut self, record: &record::Record, entry: u32) {
+        for (field, trie) in trie::INDEXED_FIELDS.iter().zip(self.tries.iter_mut()) {
+            for piece in record.indexed_words(field.record_index) {
+                trie.add(piece, entry); // Add each of the words
+            }
+            trie.header.records_count += 1;
+        }
+    }
+
+    fn save(&mut self) -> Result<(), Box<dyn error::Error>> {
+        for (field, trie) in trie::INDEXED_FIELDS.iter().zip(self.tries.iter_mut()) {
+            trie.save_to_file(&field.memory_trie_path())?;
+        }
+
+        Ok(())
+    }
+}
+
 fn clear_screen(wait: bool) {
     if wait {
         print!("Press a key to continue...");
@@ -25,38 +87,26 @@ fn clear_screen(wait: bool) {
     print!("{}[2J", 27 as char);
 }
 
-pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
+pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<dyn error::Error>> {
     clear_screen(false);
 
-    let mut name_memory_trie: trie::Trie =
-        if let Ok(new_trie) = trie::Trie::new_from_file("name_memory_trie.bin".to_string()) {
-            new_trie
-        } else {
-            trie::Trie::new()
-        };
-    let mut role_memory_trie: trie::Trie =
-        if let Ok(new_trie) = trie::Trie::new_from_file("role_memory_trie.bin".to_string()) {
-            new_trie
-        } else {
-            trie::Trie::new()
-        };
-    let mut agency_memory_trie: trie::Trie =
-        if let Ok(new_trie) = trie::Trie::new_from_file("agency_memory_trie.bin".to_string()) {
-            new_trie
-        } else {
-            trie::Trie::new()
-        };
+    check_data_dir(true)?;
+    let lock = lock_data_files(false)?;
+    check_data_files()?;
+    drop(lock);
 
     loop {
         println!("======== FEDERAL WORKER BLAMER ========");
         println!("Choose an option below: ");
-        println!("1. Generate the database (ALL YOUR DATA WILL BE LOST)");
+        println!("1. Generate the database, or add a month to it");
         println!("2. Choose a person name to be searched in the database");
         println!("3. Choose a role in the brazilian civil service to be searched in the database");
         println!("4. Choose a brazilian federal agency to be searched in the database");
-        println!("5. Insert a new worker in the database");
-        println!("6. Rebuilds the database-indexes (CAREFUL, IT WILL TAKE A WHILE)");
-        println!("7. Exit");
+        println!("5. Choose a category of workers to be searched in the database");
+        println!("6. Choose a period (or a range of them) to be searched in the database");
+        println!("7. Insert a new worker in the database");
+        println!("8. Rebuilds the database-indexes (CAREFUL, IT WILL TAKE A WHILE)");
+        println!("9. Exit");
 
         print!("Your choice: ");
         io::stdout().flush().unwrap();
@@ -66,11 +116,12 @@ pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
             read!("{}\n")
         };
 
-        if input.as_bytes().len() > 0 {
+        if !input.is_empty() {
             match input.as_bytes()[0] - 0x30 {
                 1 => {
                     println!("\nYou must pass TWO CSV files to this. The Remuneracao one, and the Cadastro one.");
-                    print!("Remuneracao file: ");
+                    println!("You may also pass the zip file downloaded from the Portal, which has both of them.");
+                    print!("Remuneracao (or zip) file: ");
                     io::stdout().flush().unwrap();
                     let remuneracao_file: String = if cfg!(windows) {
                         read!("{}\r\n")
@@ -78,24 +129,62 @@ pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
                         read!("{}\n")
                     };
 
-                    print!("Cadastro file: ");
+                    let (remuneracao_file, cadastro_file) = if CsvSource::is_zip(&remuneracao_file) {
+                        match CsvSource::from_zip(&remuneracao_file) {
+                            Ok(files) => files,
+                            Err(err) => {
+                                println!("{}", err);
+                                clear_screen(true);
+                                continue;
+                            }
+                        }
+                    } else {
+                        print!("Cadastro file: ");
+                        io::stdout().flush().unwrap();
+                        let cadastro_file: String = if cfg!(windows) {
+                            read!("{}\r\n")
+                        } else {
+                            read!("{}\n")
+                        };
+
+                        (CsvSource::File(remuneracao_file), CsvSource::File(cadastro_file))
+                    };
+
+                    print!("Add them to the current database? Otherwise ALL YOUR DATA WILL BE LOST (y/N): ");
                     io::stdout().flush().unwrap();
-                    let cadastro_file: String = if cfg!(windows) {
+                    let append: String = if cfg!(windows) {
                         read!("{}\r\n")
                     } else {
                         read!("{}\n")
                     };
-                    print!("\nThe CSV files passed in are being parsed to generate the database file.");
+                    let append = append.trim().eq_ignore_ascii_case("y");
+
+                    let category = Category::detect(&remuneracao_file.name());
+                    print!(
+                        "\nThe CSV files passed in ({}) are being parsed to generate the database file.",
+                        category
+                    );
                     io::stdout().flush().unwrap();
 
+                    let lock = lock_data_files(true)?;
                     let before: Instant = Instant::now();
-                    parser::generate_database_files(&remuneracao_file, &cadastro_file).unwrap();
+                    let mut rejects = Rejects::new(&data_dir::path(rejects::DEFAULT_REJECTS_FILE));
+                    parser::generate_database_files(
+                        &remuneracao_file,
+                        &cadastro_file,
+                        category,
+                        &parser::CsvOptions::default(),
+                        append,
+                        &mut rejects,
+                    ).unwrap();
                     println!(
                         "\nTime elapsed in the CSV parsing: {:?}",
                         Instant::now().duration_since(before)
                     );
+                    rejects.print_summary().unwrap();
 
                     reparse_tries().unwrap();
+                    drop(lock);
                     clear_screen(true);
                 }
                 2 => {
@@ -107,11 +196,13 @@ pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
                         read!("{}\n")
                     };
 
-                    let mut entries = search_person(query, prefix_search);
+                    let lock = lock_data_files(false)?;
+                    let mut entries = search_trie(query, &trie::NAME_INDEX, prefix_search);
                     entries.sort();
                     entries.dedup();
 
                     display_entries(entries);
+                    drop(lock);
                     clear_screen(true);
                 }
                 3 => {
@@ -123,11 +214,13 @@ pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
                         read!("{}\n")
                     };
 
-                    let mut entries = search_role(query, prefix_search);
+                    let lock = lock_data_files(false)?;
+                    let mut entries = search_trie(query, &trie::ROLE_INDEX, prefix_search);
                     entries.sort();
                     entries.dedup();
 
                     display_entries(entries);
+                    drop(lock);
                     clear_screen(true);
                 }
                 4 => {
@@ -139,26 +232,66 @@ pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
                         read!("{}\n")
                     };
 
-                    let mut entries = search_agency(query, prefix_search);
+                    let lock = lock_data_files(false)?;
+                    let mut entries = search_trie(query, &trie::AGENCY_INDEX, prefix_search);
                     entries.sort();
                     entries.dedup();
 
                     display_entries(entries);
+                    drop(lock);
                     clear_screen(true);
                 }
                 5 => {
-                    create_new_entry(
-                        &mut name_memory_trie,
-                        &mut role_memory_trie,
-                        &mut agency_memory_trie,
-                    ).unwrap();
+                    print!(
+                        "\nCategory of the to-be-searched workers ({}): ",
+                        category::CATEGORY_NAMES.join(", ")
+                    );
+                    io::stdout().flush().unwrap();
+                    let query: String = if cfg!(windows) {
+                        read!("{}\r\n")
+                    } else {
+                        read!("{}\n")
+                    };
+
+                    let lock = lock_data_files(false)?;
+                    let mut entries = search_category(query, prefix_search);
+                    entries.sort();
+                    entries.dedup();
+
+                    display_entries(entries);
+                    drop(lock);
                     clear_screen(true);
                 }
                 6 => {
-                    reparse_tries().unwrap();
+                    print!("\nPeriod of the to-be-searched workers (YYYYMM or YYYYMM:YYYYMM): ");
+                    io::stdout().flush().unwrap();
+                    let query: String = if cfg!(windows) {
+                        read!("{}\r\n")
+                    } else {
+                        read!("{}\n")
+                    };
+
+                    let lock = lock_data_files(false)?;
+                    match search_period(&query) {
+                        Ok(entries) => display_entries(entries),
+                        Err(err) => println!("{}", err),
+                    }
+                    drop(lock);
                     clear_screen(true);
                 }
                 7 => {
+                    let lock = lock_data_files(true)?;
+                    create_new_entry().unwrap();
+                    drop(lock);
+                    clear_screen(true);
+                }
+                8 => {
+                    let lock = lock_data_files(true)?;
+                    reparse_tries().unwrap();
+                    drop(lock);
+                    clear_screen(true);
+                }
+                9 => {
                     println!("Bye bye! It was nice to have you here!! :(");
                     break;
                 }
@@ -176,107 +309,124 @@ pub fn interactive_mode(prefix_search: bool) -> Result<(), Box<error::Error>> {
     Ok(())
 }
 
-pub fn search_on_database(matches: clap::ArgMatches, prefix_search : bool, or : bool) -> Result<(), Box<error::Error>> {
-    let mut person_entries : Vec<u32> = Vec::new();
-    let mut role_entries : Vec<u32> = Vec::new();
-    let mut agency_entries : Vec<u32> = Vec::new();
-
-    if let Some(person) = matches.value_of("person_name") {
-        person_entries = search_person(person.to_string(), prefix_search);
+// Arguments of the searches done in the tries, with the field they search
+const TRIE_SEARCHES: [(&str, &trie::IndexedField); 3] = [
+    ("person_name", &trie::NAME_INDEX),
+    ("role_name", &trie::ROLE_INDEX),
+    ("agency_name", &trie::AGENCY_INDEX),
+];
+
+// Arguments of the exact searches, with the field they search
+const LOOKUP_SEARCHES: [(&str, &LookupField); 2] = [
+    ("id", &lookup_index::ID_INDEX),
+    ("cpf", &lookup_index::CPF_INDEX),
+];
+
+// Arguments of the searches done in the salary indexes, with the lowest and the highest salary
+const SALARY_SEARCHES: [(&str, &str, &SalaryField); 2] = [
+    ("min_salary", "max_salary", &salary_index::GROSS_SALARY_INDEX),
+    ("min_net_salary", "max_net_salary", &salary_index::NET_SALARY_INDEX),
+];
+
+pub fn search_on_database(matches: clap::ArgMatches, prefix_search : bool, or : bool) -> Result<(), Box<dyn error::Error>> {
+    let mut searches: Vec<Vec<u32>> = Vec::new();
+
+    if let Some(entry) = matches.value_of("entry") {
+        searches.push(vec![parse_entry(entry)?]);
     }
 
-    if let Some(role) = matches.value_of("role_name") {
-        role_entries = search_role(role.to_string(), prefix_search);
+    for (argument, field) in TRIE_SEARCHES.iter() {
+        if let Some(query) = matches.value_of(argument) {
+            searches.push(search_trie(query.to_string(), field, prefix_search));
+        }
     }
-
-    if let Some(agency) = matches.value_of("agency_name") {
-        agency_entries = search_agency(agency.to_string(), prefix_search);
+    if let Some(query) = matches.value_of("category") {
+        searches.push(search_category(query.to_string(), prefix_search));
+    }
+    if let Some(query) = matches.value_of("period") {
+        searches.push(search_period(query)?);
+    }
+    for (argument, field) in LOOKUP_SEARCHES.iter() {
+        if let Some(value) = matches.value_of(argument) {
+            searches.push(search_lookup(field, value)?);
+        }
+    }
+    for (min_argument, max_argument, field) in SALARY_SEARCHES.iter() {
+        let min = salary_argument(&matches, min_argument)?;
+        let max = salary_argument(&matches, max_argument)?;
+        if min.is_some() || max.is_some() {
+            searches.push(search_salary(field, min, max)?);
+        }
     }
 
-    let mut entries: Vec<u32> = Vec::new();
-    if !or { // === if and
-
-        if matches.occurrences_of("person_name") > 0 {
-            if matches.occurrences_of("role_name") > 0 {
-                if matches.occurrences_of("agency_name") > 0 {
-                    // Has person, role and agency
-                    let mut partial_entries : Vec<u32> = Vec::new();
-
-                    for entry in person_entries {
-                        if role_entries.contains(&entry) { partial_entries.push(entry) }
-                    }
-
-                    for entry in partial_entries {
-                        if agency_entries.contains(&entry) { entries.push(entry) }
-                    }
-
-                } else {
-                    // Has person and role
-                    //println!("PERSON_ENTRIES: {:?} - AGENCY_ENTRIES: ");
-                    for entry in person_entries {
-                        if role_entries.contains(&entry) { entries.push(entry) }
-                    }
-
-                }
-            } else {
-                if matches.occurrences_of("agency_name") > 0 {
-                    // Has person and agency
-                    for entry in person_entries {
-                        if agency_entries.contains(&entry) { entries.push(entry) }
-                    }
-
-                } else {
-                    // Has person
-                    entries = person_entries;
-
-                }
+    let filter = DateFilter::from_matches(&matches)?;
+    if filter.is_empty() {
+        display_entries(combine_searches(searches, or));
+    } else if searches.is_empty() {
+        let before = Instant::now();
+        let mut records: Vec<(u32, record::Record)> = Vec::new();
+        parser::for_each_record(&parser::database_file(), |entry, record| {
+            if filter.matches(&record) {
+                records.push((entry, record));
             }
-        } else if matches.occurrences_of("role_name") > 0 {
-            if matches.occurrences_of("agency_name") > 0 {
-                // Has role and agency
-                for entry in role_entries {
-                    if agency_entries.contains(&entry) { entries.push(entry) }
-                }
+        })?;
+        println!(
+            "\nTime elapsed to filter the dates of the database: {:?}",
+            Instant::now().duration_since(before)
+        );
+        display_records(records);
+    } else {
+        let mut records = parser::records_from_entries(combine_searches(searches, or))?;
+        records.retain(|(_, record)| filter.matches(record));
+        display_records(records);
+    }
 
-            } else {
-                // Has role
-                entries = role_entries;
+    Ok(())
+}
 
-            }
-        } else {
-            // Has agency
-            entries = agency_entries;
+// Joins the entries found by each of the searches, keeping the ones found in all of them,
+// or in any of them when doing an OR search
+fn combine_searches(searches: Vec<Vec<u32>>, or: bool) -> Vec<u32> {
+    let mut searches = searches.into_iter();
+    let mut entries: Vec<u32> = searches.next().unwrap_or_default();
 
+    for search in searches {
+        if or {
+            entries.extend(search);
+        } else {
+            let search: HashSet<u32> = search.into_iter().collect();
+            entries.retain(|entry| search.contains(entry));
         }
-
-        display_entries(entries);
-        return Ok(())
     }
 
-    entries.append(&mut person_entries);
-    entries.append(&mut role_entries);
-    entries.append(&mut agency_entries);
     entries.sort();
     entries.dedup();
-    display_entries(entries);
-
-    Ok(())
+    entries
 }
 
 fn display_entries(entries: Vec<u32>) {
-    if entries.len() > 0 {
-        let mut csv_string: String = String::new();
-
+    if !entries.is_empty() {
         let before: Instant = Instant::now();
         let records = parser::records_from_entries(entries).unwrap();
-        for mut record in records {
-            csv_string += &record.generate_csv_string()
-        }
         println!(
             "Time elapsed to parse the records from the file: {:?}",
             Instant::now().duration_since(before)
         );
 
+        display_records(records);
+    } else {
+        println!("No search match the filters!");
+    }
+}
+
+// The entries are shown too, to be used with -e, --update and --delete
+fn display_records(records: Vec<(u32, record::Record)>) {
+    if !records.is_empty() {
+        let mut csv_string: String = String::new();
+        for (entry, mut record) in records {
+            csv_string += &format!("{};{}", entry, record.generate_csv_string())
+        }
+
         // Create the table
         let mut table = Table::from_csv(
             &mut ReaderBuilder::new()
@@ -285,7 +435,10 @@ fn display_entries(entries: Vec<u32>) {
                 .from_reader(csv_string.as_bytes()),
         );
         table.set_titles(row![
+            "Entrada",
             "Nome",
+            "Categoria",
+            "Período",
             "Cargo",
             "Orgao",
             "Salário Bruto",
@@ -303,15 +456,15 @@ fn display_entries(entries: Vec<u32>) {
     }
 }
 
-fn search_person(person: String, prefix_search : bool) -> Vec<u32> {
+fn search_trie(query: String, field: &trie::IndexedField, prefix_search : bool) -> Vec<u32> {
     let mut entries: Vec<u32> = Vec::new();
 
     let before = Instant::now();
     let mut partial_entries : Vec<u32> = Vec::new();
     let mut partial_entries_overflow : Vec<u32> = Vec::new();
-    let pieces : Vec<&str> = person.split_whitespace().collect();
+    let pieces : Vec<&str> = query.split_whitespace().collect();
     for (i, piece) in pieces.iter().enumerate() {
-        if let Some(mut entry_positions) = trie::Trie::at_from_file(&piece, "name_trie.bin", prefix_search).unwrap() {
+        if let Some(entry_positions) = trie::Trie::at_from_file(piece, &field.trie_path(), prefix_search).unwrap() {
             if i == 0 {
                 partial_entries = entry_positions;
             } else {
@@ -326,9 +479,9 @@ fn search_person(person: String, prefix_search : bool) -> Vec<u32> {
             }
         }
 
-        if fs::metadata("name_memory_trie.bin").is_ok() {
-            if let Some(mut entry_positions) =
-                trie::Trie::at_from_file(&piece, "name_memory_trie.bin", prefix_search).unwrap()
+        if fs::metadata(field.memory_trie_path()).is_ok() {
+            if let Some(entry_positions) =
+                trie::Trie::at_from_file(piece, &field.memory_trie_path(), prefix_search).unwrap()
             {
                 if i == 0 {
                     partial_entries_overflow = entry_positions;
@@ -349,237 +502,536 @@ fn search_person(person: String, prefix_search : bool) -> Vec<u32> {
     entries.append(&mut partial_entries);
     entries.append(&mut partial_entries_overflow);
 
+    // The tries still have the old words of the replaced entries, which are only kept when their
+    // current record has the words searched
+    retain_current(&mut entries, |view| {
+        has_words(&view.indexed_words(field.record_index), &pieces, prefix_search)
+    }).unwrap();
 
-    println!("\nTime elapsed to search the name trie: {:?}", Instant::now().duration_since(before));
+    println!("\nTime elapsed to search the {} trie: {:?}", field.label, Instant::now().duration_since(before));
 
-    return entries;
+    entries
 }
 
-fn search_role(role: String, prefix_search : bool) -> Vec<u32> {
-    let mut entries: Vec<u32> = Vec::new();
+// Leaves out the replaced entries whose current record doesn't match anymore what they were found
+// by in a trie, which still has their old records
+fn retain_current<F: Fn(&RecordView) -> bool>(entries: &mut Vec<u32>, matches: F) -> Result<(), Box<dyn error::Error>> {
+    let replaced = replaced::entries(&parser::database_file())?;
+    if entries.iter().any(|entry| replaced.contains(entry)) {
+        let database = MappedDatabase::open(&parser::database_file())?;
+        entries.retain(|entry| {
+            !replaced.contains(entry)
+                || match database.get(*entry) {
+                    Ok(Some(view)) => matches(&view),
+                    _ => false,
+                }
+        });
+    }
+
+    Ok(())
+}
+
+// Whether each of the pieces is one of the words, or the start of one of them in a prefix search
+fn has_words(words: &[&str], pieces: &[&str], prefix_search: bool) -> bool {
+    pieces.iter().all(|piece| {
+        words
+            .iter()
+            .any(|word| if prefix_search { word.starts_with(piece) } else { word == piece })
+    })
+}
+
+// The categories are saved with their canonical names, so "militares" finds "Militares"
+fn search_category(category: String, prefix_search : bool) -> Vec<u32> {
+    let query = match Category::from_name(category.trim()) {
+        Ok(category) => category.name().to_string(),
+        Err(_) => category,
+    };
 
+    search_trie(query, &trie::CATEGORY_INDEX, prefix_search)
+}
+
+// The period tries have a word for each period in the database, so they are gone through once,
+// keeping the periods within the range, however long it is
+fn search_period(query: &str) -> Result<Vec<u32>, Box<dyn error::Error>> {
+    let range = PeriodRange::parse(query)?;
+    let field = &trie::PERIOD_INDEX;
     let before = Instant::now();
-    let mut partial_entries : Vec<u32> = Vec::new();
-    let mut partial_entries_overflow : Vec<u32> = Vec::new();
-    let pieces : Vec<&str> = role.split_whitespace().collect();
-    for (i, piece) in pieces.iter().enumerate() {
-        if let Some(mut entry_positions) = trie::Trie::at_from_file(&piece, "role_trie.bin", prefix_search).unwrap() {
-            if i == 0 {
-                partial_entries = entry_positions;
-            } else {
-                let mut partial_entries2 : Vec<u32>= Vec::new();
-                for entry in partial_entries.clone() {
-                    if entry_positions.contains(&entry) {
-                        partial_entries2.push(entry);
-                    }
-                }
 
-                partial_entries = partial_entries2;
+    let mut entries: Vec<u32> = Vec::new();
+    for trie_file in [field.trie_path(), field.memory_trie_path()].iter() {
+        if trie_file == &field.memory_trie_path() && !Path::new(trie_file).exists() {
+            continue;
+        }
+        for (word, values) in trie::Trie::new_from_file(trie_file.to_string())?.words() {
+            if Period::parse(&word).is_ok_and(|period| range.contains(period)) {
+                entries.extend(values);
             }
         }
+    }
+    retain_current(&mut entries, |view| {
+        Period::parse(view.text(field.record_index)).is_ok_and(|period| range.contains(period))
+    })?;
 
-        if fs::metadata("role_memory_trie.bin").is_ok() {
-            if let Some(mut entry_positions) =
-                trie::Trie::at_from_file(&piece, "role_memory_trie.bin", prefix_search).unwrap()
-            {
-                if i == 0 {
-                    partial_entries_overflow = entry_positions;
-                } else {
-                    let mut partial_entries2 : Vec<u32>= Vec::new();
-                    for entry in partial_entries_overflow.clone() {
-                        if entry_positions.contains(&entry) {
-                            partial_entries2.push(entry);
-                        }
-                    }
+    entries.sort();
+    entries.dedup();
+    println!("\nTime elapsed to search the {} trie: {:?}", field.label, Instant::now().duration_since(before));
+    Ok(entries)
+}
 
-                    partial_entries_overflow = partial_entries2;
-                }
-            }
-        }
+fn salary_argument(matches: &clap::ArgMatches, argument: &str) -> Result<Option<i64>, Box<dyn error::Error>> {
+    match matches.value_of(argument) {
+        Some(value) => match money::parse(value) {
+            Some(cents) => Ok(Some(cents)),
+            None => Err(From::from(format!(
+                "Invalid salary \"{}\" for --{}, expected e.g. 27674,05",
+                value,
+                argument.replace('_', "-")
+            ))),
+        },
+        None => Ok(None),
     }
+}
 
-    entries.append(&mut partial_entries);
-    entries.append(&mut partial_entries_overflow);
+fn search_salary(
+    field: &SalaryField,
+    min: Option<i64>,
+    max: Option<i64>,
+) -> Result<Vec<u32>, Box<dyn error::Error>> {
+    let before = Instant::now();
+    let entries = salary_index::search(field, min, max)?;
+    println!(
+        "\nTime elapsed to search the {} index: {:?}",
+        field.label,
+        Instant::now().duration_since(before)
+    );
 
+    Ok(entries)
+}
 
-    println!("\nTime elapsed to search the role trie: {:?}", Instant::now().duration_since(before));
+fn search_lookup(field: &LookupField, value: &str) -> Result<Vec<u32>, Box<dyn error::Error>> {
+    let before = Instant::now();
+    let entries = lookup_index::search(field, value)?;
+    println!(
+        "\nTime elapsed to search the {} index: {:?}",
+        field.label,
+        Instant::now().duration_since(before)
+    );
 
-    return entries;
+    Ok(entries)
 }
 
-fn search_agency(agency: String, prefix_search : bool) -> Vec<u32> {
-    let mut entries: Vec<u32> = Vec::new();
+pub fn parse_entry(entry: &str) -> Result<u32, Box<dyn error::Error>> {
+    match entry.trim().parse() {
+        Ok(entry) => Ok(entry),
+        Err(_) => Err(From::from(format!(
+            "Invalid entry \"{}\", expected the number in the Entrada column",
+            entry
+        ))),
+    }
+}
 
-    let before = Instant::now();
-    let mut partial_entries : Vec<u32> = Vec::new();
-    let mut partial_entries_overflow : Vec<u32> = Vec::new();
-    let pieces : Vec<&str> = agency.split_whitespace().collect();
-    for (i, piece) in pieces.iter().enumerate() {
-        if let Some(mut entry_positions) = trie::Trie::at_from_file(&piece, "agency_trie.bin", prefix_search).unwrap() {
-            if i == 0 {
-                partial_entries = entry_positions;
-            } else {
-                let mut partial_entries2 : Vec<u32>= Vec::new();
-                for entry in partial_entries.clone() {
-                    if entry_positions.contains(&entry) {
-                        partial_entries2.push(entry);
-                    }
-                }
+// Asks for the new values of the fields of an entry, saving its new version after the records
+// of the database. Its new words are indexed by the memory tries, like the inserted workers, and
+// the searches check the replaced entries against their new records
+pub fn update_entry(entry: u32) -> Result<(), Box<dyn error::Error>> {
+    let mut record = match parser::records_from_entries(vec![entry])?.pop() {
+        Some((_, record)) => record,
+        None => return Err(From::from(format!("There is no entry {} in the database", entry))),
+    };
 
-                partial_entries = partial_entries2;
-            }
-        }
+    println!("========= ATUALIZAÇÃO DA ENTRADA {} =========\n", entry);
+    println!("Leave a field empty to keep its current value\n");
+    record.edit_from_stdin();
 
-        if fs::metadata("agency_memory_trie.bin").is_ok() {
-            if let Some(mut entry_positions) =
-                trie::Trie::at_from_file(&piece, "agency_memory_trie.bin", prefix_search).unwrap()
-            {
-                if i == 0 {
-                    partial_entries_overflow = entry_positions;
-                } else {
-                    let mut partial_entries2 : Vec<u32>= Vec::new();
-                    for entry in partial_entries_overflow.clone() {
-                        if entry_positions.contains(&entry) {
-                            partial_entries2.push(entry);
-                        }
-                    }
+    let mut database = parser::DatabaseWriter::append(&parser::database_file())?;
+    database.replace(entry, &record)?;
+    database.finish()?;
 
-                    partial_entries_overflow = partial_entries2;
-                }
-            }
+    let mut memory_tries = MemoryTries::load();
+    memory_tries.add(&record, entry);
+    memory_tries.save()?;
+    println!("========= ATUALIZAÇÃO FINALIZADA =========\n");
+
+    Ok(())
+}
+
+// The deleted entries are skipped by the searches, so the tries don't have to change
+pub fn delete_entry(entry: u32) -> Result<(), Box<dyn error::Error>> {
+    parser::delete_entry(&parser::database_file(), entry)?;
+    println!("The entry {} was deleted", entry);
+    Ok(())
+}
+
+pub fn create_new_entry() -> Result<(), Box<dyn error::Error>> {
+    println!("========= INSERÇÃO DE NOVO USUÁRIO =========\n");
+
+    let new_record = record::Record::new_from_stdin();
+    let records_len = parser::records_count(&parser::database_file())?;
+    let mut database = parser::DatabaseWriter::append(&parser::database_file())?;
+
+    // The record is saved before being indexed, so the tries never have an entry which isn't in
+    // the database, even if the program is interrupted
+    database.write(&new_record)?;
+    database.finish()?;
+
+    // Indexes each of the words of the name, role, agency and category
+    let mut memory_tries = MemoryTries::load();
+    memory_tries.add(&new_record, records_len as u32 + 1);
+    memory_tries.save()?;
+
+    println!("========= INSERÇÃO DE NOVO USUÁRIO FINALIZADA =========\n");
+
+    Ok(())
+}
+
+// The (Remuneracao, Cadastro) pairs given with -c, followed by the ones inside the zip archives
+// given with --zip
+pub fn csv_pairs(matches: &clap::ArgMatches) -> Result<Vec<(CsvSource, CsvSource)>, Box<dyn error::Error>> {
+    let mut pairs: Vec<(CsvSource, CsvSource)> = Vec::new();
+
+    if let Some(csv_files) = matches.values_of("csv") {
+        let csv_files: Vec<&str> = csv_files.collect();
+        for pair in csv_files.chunks(2) {
+            pairs.push((
+                CsvSource::File(pair[0].to_string()),
+                CsvSource::File(pair[1].to_string()),
+            ));
+        }
+    }
+    if let Some(zip_files) = matches.values_of("zip") {
+        for zip_file in zip_files {
+            pairs.push(CsvSource::from_zip(zip_file)?);
         }
     }
 
-    entries.append(&mut partial_entries);
-    entries.append(&mut partial_entries_overflow);
+    Ok(pairs)
+}
 
+// The files come in (Remuneracao, Cadastro) pairs, which are all saved in the same database.
+// The category of each pair is detected from its file names, unless one is given. The database
+// is recreated by the first pair, unless `append` is set to keep the months already loaded
+pub fn parse_csv_files(
+    csv_files: Vec<(CsvSource, CsvSource)>,
+    csv_category: Option<Category>,
+    csv_options: &parser::CsvOptions,
+    append: bool,
+    rejects: &mut Rejects,
+) -> Result<(), Box<dyn error::Error>> {
+    for (i, (remuneracao_file, cadastro_file)) in csv_files.iter().enumerate() {
+        let category =
+            csv_category.unwrap_or_else(|| Category::detect(&remuneracao_file.name()));
+        print!(
+            "The CSV files {} and {} ({}) are being parsed to generate the database file.",
+            remuneracao_file, cadastro_file, category
+        );
+        io::stdout().flush().unwrap();
 
-    println!("\nTime elapsed to search the role trie: {:?}", Instant::now().duration_since(before));
+        let before: Instant = Instant::now();
+        parser::generate_database_files(
+            remuneracao_file,
+            cadastro_file,
+            category,
+            csv_options,
+            append || i > 0,
+            rejects,
+        )?;
+        println!(
+            "\nTime elapsed in the CSV parsing: {:?}",
+            Instant::now().duration_since(before)
+        );
+    }
 
-    return entries;
+    Ok(())
 }
 
-pub fn create_new_entry(
-    name_trie: &mut trie::Trie,
-    role_trie: &mut trie::Trie,
-    agency_trie: &mut trie::Trie,
-) -> Result<(), Box<error::Error>> {
-    println!("========= INSERÇÃO DE NOVO USUÁRIO =========\n");
+pub fn prepare_csv_files(
+    matches: &clap::ArgMatches,
+    csv_category: Option<Category>,
+    csv_options: parser::CsvOptions,
+    append: bool,
+    rejects: &mut Rejects,
+) -> Result<(), Box<dyn error::Error>> {
+    let remuneracao_file = matches.value_of("remuneracao").unwrap();
+    let (remuneracao_file, cadastro_file) = if CsvSource::is_zip(remuneracao_file) {
+        CsvSource::from_zip(remuneracao_file)?
+    } else {
+        match matches.value_of("cadastro") {
+            Some(cadastro_file) => (
+                CsvSource::File(remuneracao_file.to_string()),
+                CsvSource::File(cadastro_file.to_string()),
+            ),
+            None => return Err(From::from("The Cadastro file is missing")),
+        }
+    };
+    let category = csv_category.unwrap_or_else(|| Category::detect(&remuneracao_file.name()));
+    let sorted_file = match matches.value_of("output") {
+        Some(output) => output.to_string(),
+        None => sorter::default_output_file(&remuneracao_file),
+    };
+
+    print!("The Remuneracao file is being sorted into {}.", sorted_file);
+    io::stdout().flush().unwrap();
+
+    let before: Instant = Instant::now();
+    sorter::sort_salary_file(
+        &remuneracao_file,
+        &sorted_file,
+        csv_options.salary_encoding,
+        &csv_options.aliases,
+        category,
+        rejects,
+    )?;
+    println!(
+        "\nTime elapsed sorting the Remuneracao file: {:?}",
+        Instant::now().duration_since(before)
+    );
+
+    print!(
+        "The CSV files ({}) are being parsed to generate the database file.",
+        category
+    );
+    io::stdout().flush().unwrap();
+
+    let before: Instant = Instant::now();
+    // The sorted file is always written in UTF-8
+    let csv_options = parser::CsvOptions {
+        salary_encoding: Encoding::Utf8,
+        ..csv_options
+    };
+    parser::generate_database_files(
+        &CsvSource::File(sorted_file),
+        &cadastro_file,
+        category,
+        &csv_options,
+        append,
+        rejects,
+    )?;
+    println!(
+        "\nTime elapsed in the CSV parsing: {:?}",
+        Instant::now().duration_since(before)
+    );
 
-    let records_len =
-        fs::metadata(parser::DATABASE_FILE).unwrap().len() / record::DATA_ENTRY_SIZE as u64;
-    let mut output_file = OpenOptions::new()
-        .append(true)
-        .open(parser::DATABASE_FILE)
-        .unwrap();
-    let mut new_record = record::Record::new_from_stdin();
-    new_record.resize();
+    Ok(())
+}
 
-    // Name-indexed trie insertion
-    let name = str::from_utf8(&new_record.nome)
-        .unwrap()
-        .trim_matches(char::from(0))
-        .to_string();
-    let name_split : Vec<&str> = name.split_whitespace().collect();
+pub fn diff_databases(matches: &clap::ArgMatches) -> Result<(), Box<dyn error::Error>> {
+    let from = match matches.value_of("from") {
+        Some(from) => Some(Period::parse(from)?),
+        None => None,
+    };
+    let to = match matches.value_of("to") {
+        Some(to) => Some(Period::parse(to)?),
+        None => None,
+    };
+    let threshold: f64 = match matches.value_of("threshold").unwrap().parse() {
+        Ok(threshold) => threshold,
+        Err(_) => return Err(From::from("The threshold must be a percentage, e.g. 10 or 2.5")),
+    };
 
-    for piece in name_split {
-        name_trie.add(piece.to_string(), records_len as u32 + 1); // Add each of the words
+    let database_file = parser::database_file();
+    let from_database = matches.value_of("from_database").unwrap_or(&database_file);
+    let to_database = matches.value_of("to_database").unwrap_or(&database_file);
+
+    // The database in the data directory was already locked, the other ones are locked (and
+    // recovered) the same way, so none of them is read while it is being changed
+    let mut locks: Vec<DataLock> = Vec::new();
+    if !same_file(from_database, &database_file) {
+        locks.push(lock_database(from_database, false)?);
+    }
+    if !same_file(to_database, &database_file) && !same_file(to_database, from_database) {
+        locks.push(lock_database(to_database, false)?);
     }
 
-    // Role-indexed trie insertion
-    let role = str::from_utf8(&new_record.descricao_cargo)
-        .unwrap()
-        .trim_matches(char::from(0))
-        .to_string();
-    let role_split : Vec<&str> = role.split_whitespace().collect();
+    let before: Instant = Instant::now();
+    diff::diff_periods(
+        from_database,
+        from,
+        to_database,
+        to,
+        threshold,
+        matches.value_of("output"),
+    )?;
+    println!(
+        "\nTime elapsed comparing the periods: {:?}",
+        Instant::now().duration_since(before)
+    );
+    drop(locks);
+
+    Ok(())
+}
 
-    for piece in role_split {
-        role_trie.add(piece.to_string(), records_len as u32 + 1); // Add each of the words
+// The data directory is created by the commands which generate the files, and the other ones
+// stop when it doesn't exist
+pub fn check_data_dir(create: bool) -> Result<(), Box<dyn error::Error>> {
+    let dir = data_dir::dir();
+    if create {
+        fs::create_dir_all(&dir)?;
+    } else if !Path::new(&dir).is_dir() {
+        return Err(From::from(format!("The data directory {} doesn't exist", dir)));
     }
 
+    Ok(())
+}
 
-    // Agency-indexed trie insertion
-    let agency = str::from_utf8(&new_record.orgao_exercicio)
-        .unwrap()
-        .trim_matches(char::from(0))
-        .to_string();
-    let agency_split : Vec<&str> = agency.split_whitespace().collect();
+// Locks the data files, shared to read them or exclusive to change them. The changes to the
+// database are saved in a journal, or written to temporary files, before being made, so the ones
+// which were interrupted are undone (or finished) here, before anything is read
+pub fn lock_data_files(exclusive: bool) -> Result<DataLock, Box<dyn error::Error>> {
+    lock_database(&parser::database_file(), exclusive)
+}
 
-    for piece in agency_split {
-        agency_trie.add(piece.to_string(), records_len as u32 + 1); // Add each of the words
+// Same as lock_data_files, for any database
+fn lock_database(database_file: &str, exclusive: bool) -> Result<DataLock, Box<dyn error::Error>> {
+    if exclusive || journal::needs_recovery(database_file) {
+        let lock = DataLock::exclusive(database_file)?;
+        if journal::recover(database_file)? {
+            println!("The last change to the database was interrupted, and the database was restored");
+        }
+        if exclusive {
+            return Ok(lock);
+        }
     }
 
-    name_trie.save_to_file("name_memory_trie.bin").unwrap();
-    role_trie.save_to_file("role_memory_trie.bin").unwrap();
-    agency_trie.save_to_file("agency_memory_trie.bin").unwrap();
+    DataLock::shared(database_file)
+}
 
-    output_file.write(&new_record.as_u8_array()).unwrap();
+// Whether both paths are the same file, even when written differently
+fn same_file(first: &str, second: &str) -> bool {
+    match (fs::canonicalize(first), fs::canonicalize(second)) {
+        (Ok(first), Ok(second)) => first == second,
+        _ => first == second,
+    }
+}
 
-    println!("========= INSERÇÃO DE NOVO USUÁRIO FINALIZADA =========\n");
+// Fails with a clear message when the database or the tries were written by another version of
+// the program, or aren't files of it
+pub fn check_data_files() -> Result<(), Box<dyn error::Error>> {
+    if Path::new(&parser::database_file()).exists() {
+        parser::read_header(&parser::database_file())?;
+    }
+    for field in trie::INDEXED_FIELDS.iter() {
+        for trie_file in [&field.trie_path(), &field.memory_trie_path()].iter() {
+            if Path::new(trie_file).exists() {
+                Header::read_file(trie_file, FileKind::Trie)?;
+            }
+        }
+    }
+    for field in salary_index::SALARY_FIELDS.iter() {
+        if Path::new(&field.index_path()).exists() {
+            Header::read_file(&field.index_path(), FileKind::SalaryIndex)?;
+        }
+    }
+    for field in lookup_index::LOOKUP_FIELDS.iter() {
+        if Path::new(&field.index_path()).exists() {
+            Header::read_file(&field.index_path(), FileKind::LookupIndex)?;
+        }
+    }
 
     Ok(())
 }
 
-pub fn parse_csv_files(mut csv_files: clap::Values) -> Result<(), Box<error::Error>> {
-    print!("The CSV files passed in are being parsed to generate the database file.");
-    io::stdout().flush().unwrap();
+// Looks for damage in every data file, unlike check_data_files which only reads the headers.
+// Returns whether no problem was found
+pub fn verify_data_files() -> Result<bool, Box<dyn error::Error>> {
+    println!("=============== CHECKING THE DATA FILES - PLEASE WAIT!! ===============");
+    let before = Instant::now();
+    let fine = check::verify_data_files()?;
+    if fine {
+        println!("No problems were found, in {:?}", Instant::now().duration_since(before));
+    } else {
+        println!("Problems were found, in {:?}", Instant::now().duration_since(before));
+    }
+    println!("=============== FINISHED!! ===============");
 
-    let before: Instant = Instant::now();
-    parser::generate_database_files(csv_files.next().unwrap(), csv_files.next().unwrap()).unwrap();
+    Ok(fine)
+}
+
+// The deleted and replaced records are removed from the database, which gets new entries
+pub fn compact_data_files() -> Result<(), Box<dyn error::Error>> {
+    let records_count = parser::records_count(&parser::database_file())?;
+
+    println!("=============== COMPACTING THE DATABASE - PLEASE WAIT!! ===============");
+    let before = Instant::now();
+    let kept = compact::compact_database(&parser::database_file())?;
     println!(
-        "\nTime elapsed in the CSV parsing: {:?}",
+        "{} records were kept and {} deleted entries were removed, in {:?}",
+        kept,
+        records_count - kept,
         Instant::now().duration_since(before)
     );
+    println!("=============== FINISHED!! ===============");
+
+    Ok(())
+}
+
+// The database is converted record by record, and the tries are generated again from it
+pub fn migrate_data_files() -> Result<(), Box<dyn error::Error>> {
+    let migrated = migrate::migrate_database(&parser::database_file())?;
+    migrate::migrate_memory_tries()?;
+
+    if migrated || check_data_files().is_err() {
+        reparse_tries()?;
+    } else {
+        println!("The database and the tries are already in the current version");
+    }
 
     Ok(())
 }
 
-pub fn reparse_tries() -> Result<(), Box<error::Error>> {
+pub fn reparse_tries() -> Result<(), Box<dyn error::Error>> {
     let mut threads = vec![];
 
     println!("=============== REPARSING THE TRIES - PLEASE WAIT!! ===============");
 
-    // NAME-INDEXED TRIE
-    threads.push(thread::spawn(move || {
-        println!("Generating name-indexed trie!");
-        let before = Instant::now();
-        if let Err(err) = trie::Trie::new_from_database("name_trie.bin".to_string(), 0) {
-            println!("Error trying to generate the name-indexed trie: {}", err);
-            process::exit(1);
-        }
-        println!(
-            "\nTime elapsed for name-indexed trie: {:?}",
-            Instant::now().duration_since(before)
-        );
-    }));
+    for field in trie::INDEXED_FIELDS.iter() {
+        threads.push(thread::spawn(move || {
+            println!("Generating {}-indexed trie!", field.label);
+            let before = Instant::now();
+            if let Err(err) =
+                trie::Trie::new_from_database(field.trie_path(), field.record_index)
+            {
+                println!("Error trying to generate the {}-indexed trie: {}", field.label, err);
+                process::exit(1);
+            }
+            println!(
+                "\nTime elapsed for {}-indexed trie: {:?}",
+                field.label,
+                Instant::now().duration_since(before)
+            );
+        }));
+    }
 
-    // ROLE-INDEXED TRIE
-    threads.push(thread::spawn(move || {
-        println!("Generating role-indexed trie!");
-        let before = Instant::now();
-        if let Err(err) = trie::Trie::new_from_database("role_trie.bin".to_string(), 3) {
-            println!("Error trying to generate the agency-indexed trie: {}", err);
-            process::exit(1);
-        }
-        println!(
-            "\nTime elapsed for role-indexed trie: {:?}",
-            Instant::now().duration_since(before)
-        );
-    }));
+    for field in salary_index::SALARY_FIELDS.iter() {
+        threads.push(thread::spawn(move || {
+            println!("Generating {} index!", field.label);
+            let before = Instant::now();
+            if let Err(err) = salary_index::new_from_database(field) {
+                println!("Error trying to generate the {} index: {}", field.label, err);
+                process::exit(1);
+            }
+            println!(
+                "\nTime elapsed for {} index: {:?}",
+                field.label,
+                Instant::now().duration_since(before)
+            );
+        }));
+    }
 
-    // AGENCY-INDEXED TRIE
-    threads.push(thread::spawn(move || {
-        println!("Generating agency-indexed trie!");
-        let before = Instant::now();
-        if let Err(err) = trie::Trie::new_from_database("agency_trie.bin".to_string(), 4) {
-            println!("Error trying to generate the agency-indexed trie: {}", err);
-            process::exit(1);
-        }
-        println!(
-            "\nTime elapsed for agency-indexed trie: {:?}",
-            Instant::now().duration_since(before)
-        );
-    }));
+    for field in lookup_index::LOOKUP_FIELDS.iter() {
+        threads.push(thread::spawn(move || {
+            println!("Generating {} index!", field.label);
+            let before = Instant::now();
+            if let Err(err) = lookup_index::new_from_database(field) {
+                println!("Error trying to generate the {} index: {}", field.label, err);
+                process::exit(1);
+            }
+            println!(
+                "\nTime elapsed for {} index: {:?}",
+                field.label,
+                Instant::now().duration_since(before)
+            );
+        }));
+    }
 
     for thread in threads {
         if let Err(err) = thread.join() {
@@ -588,15 +1040,12 @@ pub fn reparse_tries() -> Result<(), Box<error::Error>> {
     }
 
     // Remove old files which hold the stuff in the memory
-    if fs::metadata("name_memory_trie.bin").is_ok() {
-        fs::remove_file("name_memory_trie.bin")?;
-    }
-    if fs::metadata("role_memory_trie.bin").is_ok() {
-        fs::remove_file("role_memory_trie.bin")?;
-    }
-    if fs::metadata("agency_memory_trie.bin").is_ok() {
-        fs::remove_file("agency_memory_trie.bin")?;
+    for field in trie::INDEXED_FIELDS.iter() {
+        if fs::metadata(field.memory_trie_path()).is_ok() {
+            fs::remove_file(field.memory_trie_path())?;
+        }
     }
+    replaced::remove(&parser::database_file())?;
 
     println!("=============== FINISHED!! ===============");
     Ok(())
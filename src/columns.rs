@@ -0,0 +1,392 @@
+use csv::StringRecord;
+use std::{collections::HashMap, error::Error, fs};
+
+// Every column of the Portal files which is read to build a Record
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Column {
+    Ano,
+    Mes,
+    Id,
+    Cpf,
+    Nome,
+    RemuneracaoBasicaBruta,
+    GratificacaoNatalina,
+    Ferias,
+    OutrasRemuneracoesEventuais,
+    Irrf,
+    PssRgps,
+    DemaisDeducoes,
+    RemuneracaoAposDeducoes,
+    TotalVerbasIndenizatorias,
+    DescricaoCargo,
+    Funcao,
+    UorgExercicio,
+    OrgaoExercicio,
+    DataInicioAfastamento,
+    DataTerminoAfastamento,
+    JornadaTrabalho,
+    DataIngressoCargo,
+    DataIngressoOrgao,
+}
+
+pub const SALARY_COLUMNS: [Column; 14] = [
+    Column::Ano,
+    Column::Mes,
+    Column::Id,
+    Column::Cpf,
+    Column::Nome,
+    Column::RemuneracaoBasicaBruta,
+    Column::GratificacaoNatalina,
+    Column::Ferias,
+    Column::OutrasRemuneracoesEventuais,
+    Column::Irrf,
+    Column::PssRgps,
+    Column::DemaisDeducoes,
+    Column::RemuneracaoAposDeducoes,
+    Column::TotalVerbasIndenizatorias,
+];
+
+// Columns with values in R$, e.g. 27674,05
+pub const MONEY_COLUMNS: [Column; 9] = [
+    Column::RemuneracaoBasicaBruta,
+    Column::GratificacaoNatalina,
+    Column::Ferias,
+    Column::OutrasRemuneracoesEventuais,
+    Column::Irrf,
+    Column::PssRgps,
+    Column::DemaisDeducoes,
+    Column::RemuneracaoAposDeducoes,
+    Column::TotalVerbasIndenizatorias,
+];
+
+// Columns with dates, e.g. 01/08/2010, or "Sem informação" when there is none
+pub const DATE_COLUMNS: [Column; 4] = [
+    Column::DataInicioAfastamento,
+    Column::DataTerminoAfastamento,
+    Column::DataIngressoCargo,
+    Column::DataIngressoOrgao,
+];
+
+pub const INFO_COLUMNS: [Column; 10] = [
+    Column::Id,
+    Column::DescricaoCargo,
+    Column::Funcao,
+    Column::UorgExercicio,
+    Column::OrgaoExercicio,
+    Column::DataInicioAfastamento,
+    Column::DataTerminoAfastamento,
+    Column::JornadaTrabalho,
+    Column::DataIngressoCargo,
+    Column::DataIngressoOrgao,
+];
+
+// Header names used by the Portal for each column, in every layout we have seen since 2018.
+// The names are compared after being normalized, so case and repeated spaces don't matter
+const DEFAULT_ALIASES: [(Column, &[&str]); 23] = [
+    (Column::Ano, &["ANO"]),
+    (Column::Mes, &["MES", "MÊS"]),
+    (Column::Id, &["Id_SERVIDOR_PORTAL", "ID_SERVIDOR"]),
+    (Column::Cpf, &["CPF"]),
+    (Column::Nome, &["NOME"]),
+    (
+        Column::RemuneracaoBasicaBruta,
+        &[
+            "REMUNERAÇÃO BÁSICA BRUTA (R$)",
+            "REMUNERACAO BASICA BRUTA (R$)",
+            // Value of the jeton, in the Honorarios file
+            "VALOR (R$)",
+            "VALOR",
+        ],
+    ),
+    (
+        Column::GratificacaoNatalina,
+        &["GRATIFICAÇÃO NATALINA (R$)", "GRATIFICACAO NATALINA (R$)"],
+    ),
+    (Column::Ferias, &["FÉRIAS (R$)", "FERIAS (R$)"]),
+    (
+        Column::OutrasRemuneracoesEventuais,
+        &[
+            "OUTRAS REMUNERAÇÕES EVENTUAIS (R$)",
+            "OUTRAS REMUNERACOES EVENTUAIS (R$)",
+        ],
+    ),
+    (Column::Irrf, &["IRRF (R$)"]),
+    (Column::PssRgps, &["PSS/RPGS (R$)", "PSS/RGPS (R$)"]),
+    (
+        Column::DemaisDeducoes,
+        &["DEMAIS DEDUÇÕES (R$)", "DEMAIS DEDUCOES (R$)"],
+    ),
+    (
+        Column::RemuneracaoAposDeducoes,
+        &[
+            "REMUNERAÇÃO APÓS DEDUÇÕES OBRIGATÓRIAS (R$)",
+            "REMUNERACAO APOS DEDUCOES OBRIGATORIAS (R$)",
+        ],
+    ),
+    (
+        Column::TotalVerbasIndenizatorias,
+        &[
+            "TOTAL DE VERBAS INDENIZATÓRIAS (R$)(*)",
+            "TOTAL DE VERBAS INDENIZATÓRIAS (R$)",
+            "TOTAL DE VERBAS INDENIZATORIAS (R$)(*)",
+            "TOTAL DE VERBAS INDENIZATORIAS (R$)",
+        ],
+    ),
+    (Column::DescricaoCargo, &["DESCRICAO_CARGO"]),
+    (Column::Funcao, &["FUNCAO"]),
+    (Column::UorgExercicio, &["UORG_EXERCICIO"]),
+    (Column::OrgaoExercicio, &["ORG_EXERCICIO"]),
+    (Column::DataInicioAfastamento, &["DATA_INICIO_AFASTAMENTO"]),
+    (Column::DataTerminoAfastamento, &["DATA_TERMINO_AFASTAMENTO"]),
+    (
+        Column::JornadaTrabalho,
+        &["JORNADA_DE_TRABALHO", "JORNADA_TRABALHO"],
+    ),
+    (
+        Column::DataIngressoCargo,
+        &["DATA_INGRESSO_CARGOFUNCAO", "DATA_INGRESSO_CARGO_FUNCAO"],
+    ),
+    (Column::DataIngressoOrgao, &["DATA_INGRESSO_ORGAO"]),
+];
+
+impl Column {
+    // Name used to refer to the column in the alias files
+    pub fn key(self) -> &'static str {
+        match self {
+            Column::Ano => "ANO",
+            Column::Mes => "MES",
+            Column::Id => "ID",
+            Column::Cpf => "CPF",
+            Column::Nome => "NOME",
+            Column::RemuneracaoBasicaBruta => "REMUNERACAO_BASICA_BRUTA",
+            Column::GratificacaoNatalina => "GRATIFICACAO_NATALINA",
+            Column::Ferias => "FERIAS",
+            Column::OutrasRemuneracoesEventuais => "OUTRAS_REMUNERACOES_EVENTUAIS",
+            Column::Irrf => "IRRF",
+            Column::PssRgps => "PSS_RGPS",
+            Column::DemaisDeducoes => "DEMAIS_DEDUCOES",
+            Column::RemuneracaoAposDeducoes => "REMUNERACAO_APOS_DEDUCOES",
+            Column::TotalVerbasIndenizatorias => "TOTAL_VERBAS_INDENIZATORIAS",
+            Column::DescricaoCargo => "DESCRICAO_CARGO",
+            Column::Funcao => "FUNCAO",
+            Column::UorgExercicio => "UORG_EXERCICIO",
+            Column::OrgaoExercicio => "ORGAO_EXERCICIO",
+            Column::DataInicioAfastamento => "DATA_INICIO_AFASTAMENTO",
+            Column::DataTerminoAfastamento => "DATA_TERMINO_AFASTAMENTO",
+            Column::JornadaTrabalho => "JORNADA_TRABALHO",
+            Column::DataIngressoCargo => "DATA_INGRESSO_CARGO",
+            Column::DataIngressoOrgao => "DATA_INGRESSO_ORGAO",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Column> {
+        DEFAULT_ALIASES
+            .iter()
+            .map(|(column, _)| *column)
+            .find(|column| column.key() == key)
+    }
+}
+
+fn normalize(header: &str) -> String {
+    header
+        .trim_start_matches('\u{FEFF}')
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+// Header names accepted for each column. The names from an alias file are tried before the
+// default ones, so a new dataset vintage only needs a file with the columns that changed
+pub struct Aliases {
+    names: HashMap<Column, Vec<String>>,
+}
+
+impl Default for Aliases {
+    fn default() -> Aliases {
+        let mut names: HashMap<Column, Vec<String>> = HashMap::new();
+        for (column, aliases) in DEFAULT_ALIASES.iter() {
+            names.insert(*column, aliases.iter().map(|alias| normalize(alias)).collect());
+        }
+
+        Aliases { names }
+    }
+}
+
+impl Aliases {
+    // Reads an alias file, with one "COLUMN_KEY=Header name" per line and '#' comments
+    pub fn from_file(alias_file: &str) -> Result<Aliases, Box<dyn Error>> {
+        let mut aliases = Aliases::default();
+        let mut custom: HashMap<Column, Vec<String>> = HashMap::new();
+
+        for (number, line) in fs::read_to_string(alias_file)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, header) = match line.find('=') {
+                Some(position) => (line[..position].trim(), line[position + 1..].trim()),
+                None => {
+                    return Err(From::from(format!(
+                        "{}:{}: expected COLUMN_KEY=Header name",
+                        alias_file,
+                        number + 1
+                    )))
+                }
+            };
+
+            match Column::from_key(&key.to_uppercase()) {
+                Some(column) => custom.entry(column).or_default().push(normalize(header)),
+                None => {
+                    return Err(From::from(format!(
+                        "{}:{}: unknown column \"{}\"",
+                        alias_file,
+                        number + 1,
+                        key
+                    )))
+                }
+            }
+        }
+
+        for (column, mut names) in custom {
+            let defaults = aliases.names.remove(&column).unwrap_or_default();
+            names.extend(defaults);
+            aliases.names.insert(column, names);
+        }
+
+        Ok(aliases)
+    }
+}
+
+// Position of each column in a CSV file, resolved from its header
+pub struct ColumnMap {
+    indexes: HashMap<Column, usize>,
+}
+
+impl ColumnMap {
+    // Every column in `columns` is searched in the header, failing if one of the `required` ones
+    // isn't found. The other missing columns are left empty, with a warning
+    pub fn from_headers(
+        headers: &StringRecord,
+        columns: &[Column],
+        required: &[Column],
+        aliases: &Aliases,
+        csv_file: &str,
+    ) -> Result<ColumnMap, Box<dyn Error>> {
+        let headers: Vec<String> = headers.iter().map(normalize).collect();
+        let mut indexes: HashMap<Column, usize> = HashMap::new();
+        let mut missing: Vec<String> = Vec::new();
+        let mut missing_optional: Vec<&str> = Vec::new();
+
+        for column in columns {
+            let names = &aliases.names[column];
+            match names.iter().find_map(|name| headers.iter().position(|h| h == name)) {
+                Some(index) => {
+                    indexes.insert(*column, index);
+                }
+                None if required.contains(column) => missing.push(format!(
+                    "{} (expected one of: {})",
+                    column.key(),
+                    names.join(" | ")
+                )),
+                None => missing_optional.push(column.key()),
+            }
+        }
+
+        if !missing.is_empty() {
+            return Err(From::from(format!(
+                "The file {} is missing the following columns:\n  {}\nUse --columns with an alias file mapping them to this file's header",
+                csv_file,
+                missing.join("\n  ")
+            )));
+        }
+
+        if !missing_optional.is_empty() {
+            println!(
+                "\nThe file {} doesn't have the columns {}, they will be left empty",
+                csv_file,
+                missing_optional.join(", ")
+            );
+        }
+
+        Ok(ColumnMap { indexes })
+    }
+
+    pub fn index(&self, column: Column) -> usize {
+        self.indexes[&column]
+    }
+
+    pub fn get<'a>(&self, record: &'a StringRecord, column: Column) -> &'a str {
+        match self.indexes.get(&column) {
+            Some(index) => record.get(*index).unwrap_or(""),
+            None => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn finds_the_columns_by_their_header() {
+        // Reordered, with a byte order mark, other cases and repeated spaces
+        let headers = StringRecord::from(vec![
+            "\u{FEFF}nome",
+            "CPF",
+            "Id_SERVIDOR_PORTAL",
+            "remuneração  básica bruta (R$)",
+        ]);
+        let columns = [
+            Column::Id,
+            Column::Nome,
+            Column::RemuneracaoBasicaBruta,
+            Column::Irrf,
+        ];
+        let aliases = Aliases::default();
+        let required = [Column::Id, Column::Nome];
+        let map =
+            ColumnMap::from_headers(&headers, &columns, &required, &aliases, "a.csv").unwrap();
+
+        let row = StringRecord::from(vec!["JOSÉ", "***.123.456-**", "1234567", "1.000,00"]);
+        assert_eq!(map.index(Column::Id), 2);
+        assert_eq!(map.get(&row, Column::Nome), "JOSÉ");
+        assert_eq!(map.get(&row, Column::RemuneracaoBasicaBruta), "1.000,00");
+        assert_eq!(map.get(&row, Column::Irrf), "");
+
+        let required = [Column::Id, Column::Irrf];
+        assert!(ColumnMap::from_headers(&headers, &columns, &required, &aliases, "a.csv").is_err());
+    }
+
+    #[test]
+    fn reads_the_aliases_of_the_columns_option() {
+        let dir = env::temp_dir().join(format!("fwb_columns_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let alias_file = dir.join("aliases.txt").to_string_lossy().into_owned();
+
+        let aliases = "# 2025 files\nid = Codigo do servidor\nnome=Nome completo\n";
+        fs::write(&alias_file, aliases).unwrap();
+        let aliases = Aliases::from_file(&alias_file).unwrap();
+        let headers = StringRecord::from(vec!["NOME COMPLETO", "Codigo do  Servidor"]);
+        let columns = [Column::Id, Column::Nome];
+        let map =
+            ColumnMap::from_headers(&headers, &columns, &[Column::Id], &aliases, "a.csv").unwrap();
+        assert_eq!(map.index(Column::Id), 1);
+        assert_eq!(map.index(Column::Nome), 0);
+
+        // The default names are still accepted
+        let headers = StringRecord::from(vec!["ID_SERVIDOR"]);
+        let map =
+            ColumnMap::from_headers(&headers, &columns, &[Column::Id], &aliases, "a.csv").unwrap();
+        assert_eq!(map.index(Column::Id), 0);
+
+        fs::write(&alias_file, "SALARIO=Salario\n").unwrap();
+        assert!(Aliases::from_file(&alias_file).is_err());
+        fs::write(&alias_file, "ID Codigo\n").unwrap();
+        assert!(Aliases::from_file(&alias_file).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use atomic_file;
+use header::{FileKind, Header};
+use journal;
+use lookup_index;
+use parser::{self, DatabaseWriter};
+use record_view::MappedDatabase;
+use replaced;
+use salary_index;
+use sorted_index;
+use std::{error::Error, path::Path};
+use trie::{self, Trie};
+
+// Where a file is written while compacting, before replacing the old one
+fn compacting_file(file: &str) -> String {
+    Path::new(file)
+        .with_extension("compacting.bin")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Rewrites the database without the deleted records and the old versions of the updated ones,
+// numbering the entries again from 1. As the entries change, the tries and the indexes are
+// generated in the same pass over the records, and the files are only replaced together at the
+// end, so an error in the middle leaves the old ones as they were. Returns the quantity of records
+// kept
+pub fn compact_database(database_file: &str) -> Result<u64, Box<dyn Error>> {
+    let database = MappedDatabase::open(database_file)?;
+    let compacted_file = compacting_file(database_file);
+    let mut writer = DatabaseWriter::create(&compacted_file)?;
+    for source in database.header.sources.iter() {
+        writer.add_source(source);
+    }
+
+    let mut tries: Vec<Trie> = trie::INDEXED_FIELDS.iter().map(|_| Trie::new()).collect();
+    let mut salaries: Vec<Vec<(i64, u32)>> = salary_index::SALARY_FIELDS.iter().map(|_| Vec::new()).collect();
+    let mut lookups: Vec<Vec<(i64, u32)>> = lookup_index::LOOKUP_FIELDS.iter().map(|_| Vec::new()).collect();
+
+    let mut records_count: u32 = 0;
+    let mut write_result = Ok(());
+    database.for_each(|_, view| {
+        if write_result.is_err() {
+            return;
+        }
+        write_result = writer.write(&view.to_record());
+        records_count += 1;
+
+        for (field, trie) in trie::INDEXED_FIELDS.iter().zip(tries.iter_mut()) {
+            for piece in view.indexed_words(field.record_index) {
+                trie.add(piece.to_string(), records_count); // Add each of the words
+            }
+        }
+        for (field, entries) in salary_index::SALARY_FIELDS.iter().zip(salaries.iter_mut()) {
+            entries.push((view.money(field.record_index), records_count));
+        }
+        for (field, entries) in lookup_index::LOOKUP_FIELDS.iter().zip(lookups.iter_mut()) {
+            entries.push((lookup_index::hash(view.text(field.record_index)), records_count));
+        }
+    })?;
+    write_result?;
+    writer.finish()?;
+    drop(database);
+
+    // The tries and the indexes come from the compacted database
+    let compacted_header = Header::read_file(&compacted_file, FileKind::Database)?;
+    let mut renames: Vec<(String, String)> = vec![
+        (compacted_file.clone(), database_file.to_string()),
+        (parser::index_file(&compacted_file), parser::index_file(database_file)),
+    ];
+    for (field, mut trie) in trie::INDEXED_FIELDS.iter().zip(tries) {
+        trie.header.sources = compacted_header.sources.clone();
+        trie.header.sources_count = compacted_header.sources_count;
+        trie.header.records_count = compacted_header.records_count;
+        let trie_file = compacting_file(&field.trie_path());
+        trie.save_to_file(&trie_file)?;
+        renames.push((trie_file, field.trie_path()));
+    }
+    for (field, entries) in salary_index::SALARY_FIELDS.iter().zip(salaries) {
+        let index_file = compacting_file(&field.index_path());
+        sorted_index::save(&index_file, FileKind::SalaryIndex, &compacted_header, entries)?;
+        renames.push((index_file, field.index_path()));
+    }
+    for (field, entries) in lookup_index::LOOKUP_FIELDS.iter().zip(lookups) {
+        let index_file = compacting_file(&field.index_path());
+        sorted_index::save(&index_file, FileKind::LookupIndex, &compacted_header, entries)?;
+        renames.push((index_file, field.index_path()));
+    }
+
+    // The records inserted or replaced after the tries were generated are in them now. The memory
+    // tries and the list of replaced entries are removed with the renames, as they would point to
+    // the old entries
+    for field in trie::INDEXED_FIELDS.iter() {
+        if Path::new(&field.memory_trie_path()).exists() {
+            renames.push((field.memory_trie_path(), String::new()));
+        }
+    }
+    if Path::new(&replaced::replaced_file(database_file)).exists() {
+        renames.push((replaced::replaced_file(database_file), String::new()));
+    }
+
+    atomic_file::replace_all(&journal::commit_file(database_file), &renames)?;
+
+    Ok(compacted_header.records_count)
+}
@@ -0,0 +1,79 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+// The directory with the database, the tries, the indexes and the other files generated by the
+// program, so there can be one for each month or dataset. It is the current directory unless it
+// is set with --data-dir, the FWB_DATA_DIR variable or the configuration file
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+pub const DATA_DIR_VARIABLE: &str = "FWB_DATA_DIR";
+
+// In the home directory, with data_dir=<directory> lines (and # comments)
+pub const CONFIG_FILE: &str = ".fwb.conf";
+
+pub fn set(dir: &str) {
+    DATA_DIR.set(PathBuf::from(dir)).ok();
+}
+
+pub fn dir() -> String {
+    match DATA_DIR.get() {
+        Some(dir) => dir.to_string_lossy().into_owned(),
+        None => ".".to_string(),
+    }
+}
+
+// Where one of the generated files is, e.g. path("database.bin")
+pub fn path(file: &str) -> String {
+    match DATA_DIR.get() {
+        Some(dir) => dir.join(file).to_string_lossy().into_owned(),
+        None => file.to_string(),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// The data directory of the configuration file, if there is one. A relative directory is taken
+// from the home directory
+pub fn from_config_file() -> Result<Option<String>, Box<dyn Error>> {
+    let home = match home_dir() {
+        Some(home) => home,
+        None => return Ok(None),
+    };
+    let config_file = home.join(CONFIG_FILE);
+    if !config_file.exists() {
+        return Ok(None);
+    }
+
+    let mut data_dir = None;
+    for (number, line) in fs::read_to_string(&config_file)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.find('=') {
+            Some(position) if line[..position].trim() == "data_dir" => {
+                let dir = Path::new(line[position + 1..].trim());
+                data_dir = Some(home.join(dir).to_string_lossy().into_owned());
+            }
+            _ => {
+                return Err(From::from(format!(
+                    "{}:{}: expected data_dir=<directory>",
+                    config_file.display(),
+                    number + 1
+                )))
+            }
+        }
+    }
+
+    Ok(data_dir)
+}
@@ -0,0 +1,247 @@
+use std::{
+    error::Error,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// A day of the calendar, like the dates of admission and of leave of the Cadastro files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+// The Cadastro files use "Sem informação" (or "-") when there is no date
+pub fn is_missing(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || text == "-" || text.starts_with("Sem informa")
+}
+
+impl Date {
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Date, Box<dyn Error>> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(From::from(format!(
+                "Invalid date {:02}/{:02}/{:04}",
+                day, month, year
+            )));
+        }
+
+        Ok(Date { year, month, day })
+    }
+
+    // Accepts DD/MM/YYYY, as in the Portal files, and YYYY-MM-DD
+    pub fn parse(text: &str) -> Result<Date, Box<dyn Error>> {
+        let text = text.trim();
+        let parts: Vec<&str> = if text.contains('/') {
+            text.split('/').rev().collect()
+        } else {
+            text.split('-').collect()
+        };
+
+        match parts.as_slice() {
+            [year, month, day] => match (year.parse(), month.parse(), day.parse()) {
+                (Ok(year), Ok(month), Ok(day)) => Date::new(year, month, day),
+                _ => Err(From::from(format!(
+                    "Invalid date \"{}\", expected DD/MM/YYYY",
+                    text
+                ))),
+            },
+            _ => Err(From::from(format!(
+                "Invalid date \"{}\", expected DD/MM/YYYY",
+                text
+            ))),
+        }
+    }
+
+    // A date of the Cadastro files, which may be missing. The invalid ones are missing too, but
+    // they are sent to the rejects while loading the files
+    pub fn from_field(text: &str) -> Option<Date> {
+        if is_missing(text) {
+            None
+        } else {
+            Date::parse(text).ok()
+        }
+    }
+
+    // The day of the computer's clock, in UTC
+    pub fn today() -> Date {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() / 86400)
+            .unwrap_or(0);
+
+        // Counts the years and months since 1970-01-01
+        let mut date = Date {
+            year: 1970,
+            month: 1,
+            day: 1,
+        };
+        let mut days = days as u32;
+        loop {
+            let month_days = u32::from(days_in_month(date.year, date.month));
+            if days < month_days {
+                date.day = days as u8 + 1;
+                return date;
+            }
+            days -= month_days;
+            if date.month == 12 {
+                date.year += 1;
+                date.month = 1;
+            } else {
+                date.month += 1;
+            }
+        }
+    }
+
+    // The last day of a month, e.g. for the end of a period
+    pub fn end_of_month(year: u16, month: u8) -> Date {
+        Date {
+            year,
+            month,
+            day: days_in_month(year, month),
+        }
+    }
+
+    // Complete years from this date until `until`, or 0 if it is before this date
+    pub fn years_until(self, until: Date) -> u16 {
+        if until <= self {
+            return 0;
+        }
+
+        let years = until.year - self.year;
+        if (until.month, until.day) < (self.month, self.day) {
+            years - 1
+        } else {
+            years
+        }
+    }
+
+    // Saved in the database as the number YYYYMMDD, with 0 for the missing dates
+    pub fn to_number(date: Option<Date>) -> u32 {
+        date.map_or(0, |date| {
+            u32::from(date.year) * 10000 + u32::from(date.month) * 100 + u32::from(date.day)
+        })
+    }
+
+    pub fn from_number(number: u32) -> Option<Date> {
+        if number == 0 {
+            return None;
+        }
+
+        Date::new(
+            (number / 10000) as u16,
+            (number / 100 % 100) as u8,
+            (number % 100) as u8,
+        ).ok()
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}/{:02}/{:04}", self.day, self.month, self.year)
+    }
+}
+
+// Years of admission searched with --admission-year, a single one (2010) or an inclusive range
+// (2010:2015)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YearRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl YearRange {
+    pub fn parse(text: &str) -> Result<YearRange, Box<dyn Error>> {
+        let mut parts = text.splitn(2, ':');
+        let start = parse_year(parts.next().unwrap_or(""))?;
+        let end = match parts.next() {
+            Some(end) => parse_year(end)?,
+            None => start,
+        };
+
+        if start > end {
+            return Err(From::from(format!(
+                "The year range {} ends before it starts",
+                text
+            )));
+        }
+
+        Ok(YearRange { start, end })
+    }
+
+    pub fn contains(self, year: u16) -> bool {
+        self.start <= year && year <= self.end
+    }
+}
+
+fn parse_year(text: &str) -> Result<u16, Box<dyn Error>> {
+    match text.trim().parse() {
+        Ok(year) => Ok(year),
+        Err(_) => Err(From::from(format!("Invalid year \"{}\", expected YYYY", text))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_dates_of_the_files() {
+        let date = Date::new(2010, 2, 1).unwrap();
+        assert_eq!(Date::parse("01/02/2010").unwrap(), date);
+        assert_eq!(Date::parse(" 2010-02-01 ").unwrap(), date);
+        assert_eq!(date.to_string(), "01/02/2010");
+        assert_eq!(Date::from_number(Date::to_number(Some(date))), Some(date));
+        assert_eq!(Date::parse("29/02/2020").unwrap(), Date::new(2020, 2, 29).unwrap());
+
+        let texts = [
+            "29/02/2019",
+            "31/04/2010",
+            "00/01/2010",
+            "01/13/2010",
+            "01/02",
+            "a/b/c",
+            "",
+        ];
+        for text in texts.iter() {
+            assert!(Date::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn reads_the_missing_dates_as_none() {
+        assert_eq!(Date::from_field("Sem informação"), None);
+        assert_eq!(Date::from_field("-"), None);
+        assert_eq!(Date::from_field(""), None);
+        assert_eq!(Date::from_field("31/02/2010"), None);
+        assert_eq!(Date::from_number(0), None);
+        assert_eq!(Date::to_number(None), 0);
+        assert_eq!(Date::from_field("15/03/2005"), Some(Date::new(2005, 3, 15).unwrap()));
+    }
+
+    #[test]
+    fn counts_the_complete_years() {
+        let admission = Date::parse("15/03/2005").unwrap();
+        assert_eq!(admission.years_until(Date::parse("14/03/2015").unwrap()), 9);
+        assert_eq!(admission.years_until(Date::parse("15/03/2015").unwrap()), 10);
+        assert_eq!(admission.years_until(Date::parse("01/01/2000").unwrap()), 0);
+        assert_eq!(
+            YearRange::parse("2010:2015").unwrap(),
+            YearRange {
+                start: 2010,
+                end: 2015
+            }
+        );
+        assert!(YearRange::parse("2015:2010").is_err());
+    }
+}
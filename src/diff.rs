@@ -0,0 +1,262 @@
+use csv::Writer;
+use money;
+use parser;
+use period::Period;
+use prettytable::{format, Table};
+use record;
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap},
+    error::Error,
+};
+
+// What is compared of each worker between the two periods
+struct Snapshot {
+    name: String,
+    role: String,
+    agency: String,
+    // In cents
+    gross_salary: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    Appeared,
+    Disappeared,
+    Role,
+    Agency,
+    Salary,
+}
+
+impl Change {
+    fn label(self) -> &'static str {
+        match self {
+            Change::Appeared => "Entrou",
+            Change::Disappeared => "Saiu",
+            Change::Role => "Cargo",
+            Change::Agency => "Órgão",
+            Change::Salary => "Salário Bruto",
+        }
+    }
+}
+
+struct DiffEntry {
+    change: Change,
+    id: String,
+    name: String,
+    before: String,
+    after: String,
+}
+
+// Reads the workers of a period, keyed by Id_SERVIDOR_PORTAL. A worker with more than one record
+// in the period keeps the role and agency of the first one, with the salaries summed up.
+// Without a period, the database must have only one
+fn load_snapshots(
+    database_file: &str,
+    period: Option<Period>,
+) -> Result<HashMap<String, Snapshot>, Box<dyn Error>> {
+    let wanted_period: Option<String> = period.map(|period| period.to_string());
+    let mut periods: BTreeSet<String> = BTreeSet::new();
+    let mut snapshots: HashMap<String, Snapshot> = HashMap::new();
+    let period_field = record::field_index("PERIODO");
+    let id_field = record::field_index("ID");
+    let name_field = record::field_index("NOME");
+    let role_field = record::field_index("DESCRICAO_CARGO");
+    let agency_field = record::field_index("ORGAO_EXERCICIO");
+
+    parser::for_each_record(database_file, |_, record| {
+        let record_period = record.get(period_field);
+        match wanted_period {
+            Some(ref wanted_period) if *wanted_period != record_period => return,
+            Some(_) => (),
+            None => {
+                periods.insert(record_period);
+            }
+        }
+
+        let gross_salary = record.remuneracao_basica_bruta_rs;
+        match snapshots.entry(record.get(id_field)) {
+            Entry::Occupied(mut entry) => entry.get_mut().gross_salary += gross_salary,
+            Entry::Vacant(entry) => {
+                entry.insert(Snapshot {
+                    name: record.get(name_field),
+                    role: record.get(role_field),
+                    agency: record.get(agency_field),
+                    gross_salary,
+                });
+            }
+        }
+    })?;
+
+    if periods.len() > 1 {
+        return Err(From::from(format!(
+            "The database {} has the periods {}, choose one of them",
+            database_file,
+            periods.into_iter().collect::<Vec<String>>().join(", ")
+        )));
+    }
+    if snapshots.is_empty() {
+        return Err(From::from(match wanted_period {
+            Some(wanted_period) => format!(
+                "The database {} has no records of the period {}",
+                database_file, wanted_period
+            ),
+            None => format!("The database {} has no records", database_file),
+        }));
+    }
+
+    Ok(snapshots)
+}
+
+// Changes of the gross salary are only reported when they are bigger than `threshold` percent of
+// the salary of the first period
+fn compare(
+    before: &HashMap<String, Snapshot>,
+    after: &HashMap<String, Snapshot>,
+    threshold: f64,
+) -> Vec<DiffEntry> {
+    let mut entries: Vec<DiffEntry> = Vec::new();
+
+    for (id, old) in before.iter() {
+        let new = match after.get(id) {
+            Some(new) => new,
+            None => {
+                entries.push(DiffEntry {
+                    change: Change::Disappeared,
+                    id: id.clone(),
+                    name: old.name.clone(),
+                    before: old.role.clone(),
+                    after: String::new(),
+                });
+                continue;
+            }
+        };
+
+        if old.role != new.role {
+            entries.push(DiffEntry {
+                change: Change::Role,
+                id: id.clone(),
+                name: new.name.clone(),
+                before: old.role.clone(),
+                after: new.role.clone(),
+            });
+        }
+        if old.agency != new.agency {
+            entries.push(DiffEntry {
+                change: Change::Agency,
+                id: id.clone(),
+                name: new.name.clone(),
+                before: old.agency.clone(),
+                after: new.agency.clone(),
+            });
+        }
+
+        let difference = (new.gross_salary - old.gross_salary).abs();
+        let changed = if old.gross_salary == 0 {
+            difference > 0
+        } else {
+            difference as f64 * 100.0 / old.gross_salary.abs() as f64 > threshold
+        };
+        if changed {
+            entries.push(DiffEntry {
+                change: Change::Salary,
+                id: id.clone(),
+                name: new.name.clone(),
+                before: money::format(old.gross_salary),
+                after: money::format(new.gross_salary),
+            });
+        }
+    }
+
+    for (id, new) in after.iter() {
+        if !before.contains_key(id) {
+            entries.push(DiffEntry {
+                change: Change::Appeared,
+                id: id.clone(),
+                name: new.name.clone(),
+                before: String::new(),
+                after: new.role.clone(),
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| (a.change, &a.name, &a.id).cmp(&(b.change, &b.name, &b.id)));
+    entries
+}
+
+const DIFF_TITLES: [&str; 5] = ["Mudança", "ID", "Nome", "Antes", "Depois"];
+
+fn print_entries(entries: &[DiffEntry]) {
+    for change in [
+        Change::Appeared,
+        Change::Disappeared,
+        Change::Role,
+        Change::Agency,
+        Change::Salary,
+    ].iter()
+    {
+        println!(
+            "{}: {}",
+            change.label(),
+            entries.iter().filter(|entry| entry.change == *change).count()
+        );
+    }
+
+    if entries.is_empty() {
+        println!("No differences between the periods!");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_titles(DIFF_TITLES.iter().collect());
+    for entry in entries {
+        table.add_row(row![
+            entry.change.label(),
+            entry.id,
+            entry.name,
+            entry.before,
+            entry.after
+        ]);
+    }
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.printstd();
+}
+
+fn write_entries(entries: &[DiffEntry], output_file: &str) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_path(output_file)?;
+    writer.write_record(DIFF_TITLES.iter())?;
+    for entry in entries {
+        writer.write_record([
+            entry.change.label(),
+            &entry.id,
+            &entry.name,
+            &entry.before,
+            &entry.after,
+        ])?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Lists the workers who appeared, disappeared, changed their role or agency or had their gross
+// salary changed between two periods, which may be in the same database or in different ones
+pub fn diff_periods(
+    from_database: &str,
+    from: Option<Period>,
+    to_database: &str,
+    to: Option<Period>,
+    threshold: f64,
+    output_file: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let before = load_snapshots(from_database, from)?;
+    let after = load_snapshots(to_database, to)?;
+    let entries = compare(&before, &after, threshold);
+
+    print_entries(&entries);
+    if let Some(output_file) = output_file {
+        write_entries(&entries, output_file)?;
+        println!("The differences were written to {}", output_file);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use encoding_rs::{UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use source::CsvSource;
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+    str,
+};
+
+// Quantity of bytes read from the beginning of the file to guess its encoding
+const SNIFF_SIZE: usize = 64 * 1024;
+
+pub const ENCODING_NAMES: [&str; 4] = ["auto", "utf-8", "latin1", "cp1252"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Auto,
+    Utf8,
+    Latin1,
+    Windows1252,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Result<Encoding, Box<dyn Error>> {
+        match name.to_lowercase().as_str() {
+            "auto" => Ok(Encoding::Auto),
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            "cp1252" | "windows-1252" => Ok(Encoding::Windows1252),
+            _ => Err(From::from(format!(
+                "Unknown encoding \"{}\", expected one of: {}",
+                name,
+                ENCODING_NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+// The files from the Portal da Transparencia are ISO-8859-1/Windows-1252, but some of them were
+// already converted to UTF-8 by someone, so we check if the beginning of the file is valid UTF-8
+fn guess_encoding(sample: &[u8]) -> Encoding {
+    match str::from_utf8(sample) {
+        Ok(_) => Encoding::Utf8,
+        // The sample may have cut a multi-byte character in half at its end
+        Err(err) if err.error_len().is_none() => Encoding::Utf8,
+        Err(_) => Encoding::Windows1252,
+    }
+}
+
+// Opens a CSV file transcoding it to UTF-8 on the fly, so it can be read by the csv crate
+pub fn open(source: &CsvSource, encoding: Encoding) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let mut file = source.open()?;
+    let mut sample: Vec<u8> = Vec::with_capacity(SNIFF_SIZE);
+    (&mut file).take(SNIFF_SIZE as u64).read_to_end(&mut sample)?;
+
+    let encoding = match encoding {
+        Encoding::Auto => guess_encoding(&sample),
+        other => other,
+    };
+
+    // ISO-8859-1 is decoded as Windows-1252, as both agree in every printable character
+    let decoder = match encoding {
+        Encoding::Utf8 => UTF_8,
+        _ => WINDOWS_1252,
+    };
+
+    Ok(Box::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(Some(decoder))
+            .build(Cursor::new(sample).chain(file)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser;
+    use std::{env, fs, process};
+
+    fn read(file: &str, encoding: Encoding) -> String {
+        let mut text = String::new();
+        open(&CsvSource::File(file.to_string()), encoding)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn decodes_the_files_of_the_portal() {
+        let dir = env::temp_dir().join(format!("fwb_encoding_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("201807_Cadastro.csv").to_string_lossy().into_owned();
+
+        // "Sem informação" and "JOSÉ" in ISO-8859-1, and the euro sign only Windows-1252 has
+        fs::write(&file, b"Sem informa\xe7\xe3o;JOS\xc9;\x80 1,00\n").unwrap();
+        for encoding in [Encoding::Auto, Encoding::Latin1, Encoding::Windows1252].iter() {
+            let text = read(&file, *encoding);
+            assert_eq!(text, "Sem informação;JOSÉ;€ 1,00\n");
+            assert!(parser::is_sem_informacao(text.split(';').next().unwrap()));
+        }
+
+        // Read as UTF-8, the accents are lost, but the missing value is still recognized
+        let text = read(&file, Encoding::Utf8);
+        assert!(parser::is_sem_informacao(text.split(';').next().unwrap()));
+
+        fs::write(&file, "Sem informação;JOSÉ\n").unwrap();
+        assert_eq!(read(&file, Encoding::Auto), "Sem informação;JOSÉ\n");
+        assert_eq!(read(&file, Encoding::Utf8), "Sem informação;JOSÉ\n");
+
+        assert_eq!(Encoding::from_name("ISO-8859-1").unwrap(), Encoding::Latin1);
+        assert!(Encoding::from_name("utf-16").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
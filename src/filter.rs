@@ -0,0 +1,94 @@
+use clap;
+use date::{Date, YearRange};
+use period::Period;
+use record::{self, Record};
+use std::error::Error;
+
+// Filters on the dates of the records, which aren't indexed in the tries, so they are checked on
+// the records found by the other searches, or on the whole database when there are none
+#[derive(Default)]
+pub struct DateFilter {
+    admission_years: Option<YearRange>,
+    min_tenure: Option<u16>,
+    max_tenure: Option<u16>,
+    on_leave: Option<Date>,
+}
+
+fn parse_years(text: &str) -> Result<u16, Box<dyn Error>> {
+    match text.trim().parse() {
+        Ok(years) => Ok(years),
+        Err(_) => Err(From::from(format!(
+            "Invalid tenure \"{}\", expected a number of years",
+            text
+        ))),
+    }
+}
+
+impl DateFilter {
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<DateFilter, Box<dyn Error>> {
+        let mut filter = DateFilter::default();
+        if let Some(years) = matches.value_of("admission_year") {
+            filter.admission_years = Some(YearRange::parse(years)?);
+        }
+        if let Some(years) = matches.value_of("min_tenure") {
+            filter.min_tenure = Some(parse_years(years)?);
+        }
+        if let Some(years) = matches.value_of("max_tenure") {
+            filter.max_tenure = Some(parse_years(years)?);
+        }
+        if let Some(day) = matches.value_of("on_leave") {
+            filter.on_leave = Some(if day == "today" {
+                Date::today()
+            } else {
+                Date::parse(day)?
+            });
+        }
+
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.admission_years.is_none()
+            && self.min_tenure.is_none()
+            && self.max_tenure.is_none()
+            && self.on_leave.is_none()
+    }
+
+    // The tenure is counted until the end of the period of the record, so the workers of an old
+    // payroll have the tenure they had back then. Without a period, it is counted until today
+    pub fn matches(&self, record: &Record) -> bool {
+        let admission = record.admission_date();
+
+        if let Some(years) = self.admission_years {
+            match admission {
+                Some(admission) if years.contains(admission.year) => {}
+                _ => return false,
+            }
+        }
+
+        if self.min_tenure.is_some() || self.max_tenure.is_some() {
+            let admission = match admission {
+                Some(admission) => admission,
+                None => return false,
+            };
+            let until = match Period::parse(&record.get(record::PERIODO)) {
+                Ok(period) => Date::end_of_month(period.year, period.month),
+                Err(_) => Date::today(),
+            };
+            let tenure = admission.years_until(until);
+            if self.min_tenure.is_some_and(|min| tenure < min)
+                || self.max_tenure.is_some_and(|max| tenure > max)
+            {
+                return false;
+            }
+        }
+
+        if let Some(day) = self.on_leave {
+            if !record.is_on_leave(day) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
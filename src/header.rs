@@ -0,0 +1,235 @@
+use crc32fast::Hasher;
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Every database and trie file starts with a header of this size, so the data after it is always
+// in the same place, even when the header changes (e.g. when files are appended to the database)
+pub const HEADER_SIZE: u64 = 4096;
+
+// Versions of the formats of the files. Version 1 of the database had the same size for every
+// record and version 2 had variable-length records, both without a header, version 3 had the money
+// as text and version 4 had the dates as text. Version 1 of the tries didn't have a header,
+// version 2 had only 1 byte for the character of each child and version 3 had 3 bytes for the
+// quantity of values and 1 byte for the quantity of children of each node
+pub const DATABASE_VERSION: u16 = 5;
+pub const TRIE_VERSION: u16 = 4;
+pub const SALARY_INDEX_VERSION: u16 = 1;
+pub const LOOKUP_INDEX_VERSION: u16 = 1;
+
+// magic (4 bytes), version (2), creation time (8), records count (8), checksum (4) and the
+// quantity of source files (4), before the names of the source files
+const FIXED_HEADER_SIZE: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileKind {
+    Database,
+    Trie,
+    SalaryIndex,
+    LookupIndex,
+}
+
+impl FileKind {
+    pub fn magic(self) -> &'static [u8; 4] {
+        match self {
+            FileKind::Database => b"FWBD",
+            FileKind::Trie => b"FWBT",
+            FileKind::SalaryIndex => b"FWBS",
+            FileKind::LookupIndex => b"FWBL",
+        }
+    }
+
+    pub fn version(self) -> u16 {
+        match self {
+            FileKind::Database => DATABASE_VERSION,
+            FileKind::Trie => TRIE_VERSION,
+            FileKind::SalaryIndex => SALARY_INDEX_VERSION,
+            FileKind::LookupIndex => LOOKUP_INDEX_VERSION,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FileKind::Database => "database",
+            FileKind::Trie => "trie",
+            FileKind::SalaryIndex => "salary index",
+            FileKind::LookupIndex => "lookup index",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub kind: FileKind,
+    pub version: u16,
+    // Seconds since the UNIX epoch
+    pub created: u64,
+    pub records_count: u64,
+    // CRC32 of everything after the header
+    pub checksum: u32,
+    // The CSV files the records came from. Only the names which fit in the header are kept, but
+    // all of them are counted
+    pub sources: Vec<String>,
+    pub sources_count: u32,
+}
+
+impl Header {
+    pub fn new(kind: FileKind) -> Header {
+        Header {
+            kind,
+            version: kind.version(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+            records_count: 0,
+            checksum: 0,
+            sources: Vec::new(),
+            sources_count: 0,
+        }
+    }
+
+    pub fn add_source(&mut self, source: &str) {
+        if !self.sources.iter().any(|known| known == source) {
+            self.sources.push(source.to_string());
+            self.sources_count += 1;
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_SIZE as usize);
+        bytes.extend_from_slice(self.kind.magic());
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.created.to_le_bytes());
+        bytes.extend_from_slice(&self.records_count.to_le_bytes());
+        bytes.extend_from_slice(&self.checksum.to_le_bytes());
+        bytes.extend_from_slice(&self.sources_count.to_le_bytes());
+
+        // Each name has its length (2 bytes) before it, and a zero length ends them
+        for source in self.sources.iter() {
+            if bytes.len() + 2 + source.len() + 2 > HEADER_SIZE as usize {
+                break;
+            }
+            bytes.extend_from_slice(&(source.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(source.as_bytes());
+        }
+
+        bytes.resize(HEADER_SIZE as usize, 0);
+        bytes
+    }
+
+    // Reads the header at the beginning of `reader`, leaving it at the data after it. Fails when
+    // the file isn't of the expected kind or was written with another version of the format
+    pub fn read<R: Read>(reader: &mut R, file: &str, kind: FileKind) -> Result<Header, Box<dyn Error>> {
+        let header = Header::read_any_version(reader, file, kind)?;
+        if header.version != kind.version() {
+            return Err(From::from(format!(
+                "{} was written with the version {} of the {} format, but this program uses the version {} ({})",
+                file,
+                header.version,
+                kind.name(),
+                kind.version(),
+                if header.version < kind.version() {
+                    "run `migrate` to convert it"
+                } else {
+                    "update the program to read it"
+                }
+            )));
+        }
+
+        Ok(header)
+    }
+
+    // Reads the header of a file from any version which has one, to convert it
+    pub fn read_any_version<R: Read>(
+        reader: &mut R,
+        file: &str,
+        kind: FileKind,
+    ) -> Result<Header, Box<dyn Error>> {
+        let mut bytes = vec![0; HEADER_SIZE as usize];
+        if let Err(err) = reader.read_exact(&mut bytes) {
+            if err.kind() != io::ErrorKind::UnexpectedEof {
+                return Err(From::from(err));
+            }
+            bytes.clear();
+        }
+
+        if bytes.len() < FIXED_HEADER_SIZE || &bytes[0..4] != kind.magic() {
+            return Err(From::from(format!(
+                "{} isn't a {} file of this program, or was written by an old version of it (run `migrate` to convert it)",
+                file,
+                kind.name()
+            )));
+        }
+
+        let mut header = Header {
+            kind,
+            version: u16::from_le_bytes([bytes[4], bytes[5]]),
+            created: u64_at(&bytes, 6),
+            records_count: u64_at(&bytes, 14),
+            checksum: u32_at(&bytes, 22),
+            sources: Vec::new(),
+            sources_count: u32_at(&bytes, 26),
+        };
+
+        let mut position = FIXED_HEADER_SIZE;
+        while position + 2 <= bytes.len() {
+            let length = u16::from_le_bytes([bytes[position], bytes[position + 1]]) as usize;
+            if length == 0 || position + 2 + length > bytes.len() {
+                break;
+            }
+            header
+                .sources
+                .push(String::from_utf8_lossy(&bytes[position + 2..position + 2 + length]).into_owned());
+            position += 2 + length;
+        }
+
+        Ok(header)
+    }
+
+    pub fn read_file(file: &str, kind: FileKind) -> Result<Header, Box<dyn Error>> {
+        Header::read(&mut File::open(file)?, file, kind)
+    }
+}
+
+fn u64_at(bytes: &[u8], position: usize) -> u64 {
+    let mut value = [0; 8];
+    value.copy_from_slice(&bytes[position..position + 8]);
+    u64::from_le_bytes(value)
+}
+
+fn u32_at(bytes: &[u8], position: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[position..position + 4]);
+    u32::from_le_bytes(value)
+}
+
+// Computes the checksum of the data read through it, to be compared with the one in the header
+pub struct ChecksumReader<R: Read> {
+    inner: R,
+    hasher: Hasher,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> ChecksumReader<R> {
+        ChecksumReader {
+            inner,
+            hasher: Hasher::new(),
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.hasher.update(&buffer[..read]);
+        Ok(read)
+    }
+}
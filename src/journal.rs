@@ -0,0 +1,229 @@
+use atomic_file;
+use header::HEADER_SIZE;
+use parser;
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+// The changes made in place to a database and its index (appending records, updating and
+// deleting entries) are undone after an interruption with a journal, e.g. database.journal,
+// written before them. It has the header of the database, the sizes of the database and of the
+// index (8 bytes each) and the old offset of each entry of the index which is changed (4 bytes
+// for the entry and 8 for the offset). It is removed when the changes are complete
+const JOURNAL_START_SIZE: usize = HEADER_SIZE as usize + 16;
+const JOURNAL_ENTRY_SIZE: usize = 12;
+
+pub fn journal_file(database_file: &str) -> String {
+    Path::new(database_file)
+        .with_extension("journal")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Lists the renames of the database and its index when they are replaced
+pub fn commit_file(database_file: &str) -> String {
+    Path::new(database_file)
+        .with_extension("commit")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut number = [0; 8];
+    number.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(number)
+}
+
+pub struct Journal {
+    file: File,
+    journal_file: String,
+}
+
+impl Journal {
+    pub fn begin(database_file: &str) -> Result<Journal, Box<dyn Error>> {
+        let mut database = File::open(database_file)?;
+        let mut bytes: Vec<u8> = vec![0; HEADER_SIZE as usize];
+        database.read_exact(&mut bytes)?;
+        bytes.extend_from_slice(&database.metadata()?.len().to_le_bytes());
+        bytes.extend_from_slice(&fs::metadata(parser::index_file(database_file))?.len().to_le_bytes());
+
+        let journal_file = journal_file(database_file);
+        let mut file = File::create(&journal_file)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+
+        Ok(Journal { file, journal_file })
+    }
+
+    // Saves the offset of an entry of the index before it is changed
+    pub fn save_entry(&mut self, entry: u32, offset: u64) -> io::Result<()> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(JOURNAL_ENTRY_SIZE);
+        bytes.extend_from_slice(&entry.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        self.file.write_all(&bytes)?;
+        self.file.sync_all()
+    }
+
+    // The changes are in the disk, so they don't have to be undone anymore
+    pub fn commit(self) -> io::Result<()> {
+        drop(self.file);
+        fs::remove_file(self.journal_file)
+    }
+}
+
+// Whether a change to the database was interrupted, or is being made by another process
+pub fn needs_recovery(database_file: &str) -> bool {
+    [
+        journal_file(database_file),
+        commit_file(database_file),
+        atomic_file::temp_file(database_file),
+        atomic_file::temp_file(&parser::index_file(database_file)),
+    ].iter()
+        .any(|file| Path::new(file).exists())
+}
+
+// Leaves the database as it was before or after the last change, when it was interrupted.
+// Returns false when there was nothing to do
+pub fn recover(database_file: &str) -> Result<bool, Box<dyn Error>> {
+    let index_file = parser::index_file(database_file);
+    let mut recovered = atomic_file::recover(&commit_file(database_file))?;
+
+    // A database which was being created, and wasn't renamed yet, is left out
+    for temp_file in [atomic_file::temp_file(database_file), atomic_file::temp_file(&index_file)].iter() {
+        if Path::new(temp_file).exists() {
+            fs::remove_file(temp_file)?;
+            recovered = true;
+        }
+    }
+
+    let journal_file = journal_file(database_file);
+    if !Path::new(&journal_file).exists() {
+        return Ok(recovered);
+    }
+
+    // Nothing was changed before the beginning of the journal was in the disk
+    let mut bytes: Vec<u8> = Vec::new();
+    File::open(&journal_file)?.read_to_end(&mut bytes)?;
+    if bytes.len() >= JOURNAL_START_SIZE {
+        let mut database = OpenOptions::new().write(true).open(database_file)?;
+        database.write_all(&bytes[..HEADER_SIZE as usize])?;
+        database.set_len(read_u64(&bytes[HEADER_SIZE as usize..]))?;
+        database.sync_all()?;
+
+        let mut index = OpenOptions::new().write(true).open(&index_file)?;
+        index.set_len(read_u64(&bytes[HEADER_SIZE as usize + 8..]))?;
+        // Backwards, so an entry changed twice gets its first offset back
+        for saved in bytes[JOURNAL_START_SIZE..].chunks_exact(JOURNAL_ENTRY_SIZE).rev() {
+            let entry = u32::from_le_bytes([saved[0], saved[1], saved[2], saved[3]]);
+            if entry > 0 {
+                index.seek(SeekFrom::Start(u64::from(entry - 1) * 8))?;
+                index.write_all(&saved[4..])?;
+            }
+        }
+        index.sync_all()?;
+    }
+
+    fs::remove_file(journal_file)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use record::{self, Record};
+    use std::{env, process};
+
+    // A database with two records, in a directory of its own
+    fn test_database(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("fwb_journal_{}_{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let database_file = dir.join("database.bin").to_string_lossy().into_owned();
+
+        let mut writer = parser::DatabaseWriter::create(&database_file).unwrap();
+        for name in ["ANA", "BRUNO"].iter() {
+            let mut record = Record::default();
+            record.set(record::field_index("NOME"), name);
+            writer.write(&record).unwrap();
+        }
+        writer.finish().unwrap();
+
+        database_file
+    }
+
+    fn remove_database(database_file: &str) {
+        fs::remove_dir_all(Path::new(database_file).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn recover_undoes_an_interrupted_change() {
+        let database_file = test_database("undo");
+        let index_file = parser::index_file(&database_file);
+        let database = fs::read(&database_file).unwrap();
+        let index = fs::read(&index_file).unwrap();
+        assert!(!needs_recovery(&database_file));
+
+        // Changes the header, appends a record and points the first entry to it, like an update
+        // which was interrupted before its commit
+        let mut journal = Journal::begin(&database_file).unwrap();
+        journal.save_entry(1, read_u64(&index)).unwrap();
+        let mut changed = OpenOptions::new().write(true).open(&database_file).unwrap();
+        changed.write_all(&[0xff; 16]).unwrap();
+        changed.seek(SeekFrom::End(0)).unwrap();
+        changed.write_all(b"a record which wasn't finished").unwrap();
+        let mut changed = OpenOptions::new().write(true).open(&index_file).unwrap();
+        changed.write_all(&(database.len() as u64).to_le_bytes()).unwrap();
+        changed.seek(SeekFrom::End(0)).unwrap();
+        changed.write_all(&[0; 8]).unwrap();
+        drop(journal);
+
+        assert!(needs_recovery(&database_file));
+        assert!(recover(&database_file).unwrap());
+        assert_eq!(fs::read(&database_file).unwrap(), database);
+        assert_eq!(fs::read(&index_file).unwrap(), index);
+        assert!(!needs_recovery(&database_file));
+        assert!(!recover(&database_file).unwrap());
+
+        remove_database(&database_file);
+    }
+
+    #[test]
+    fn recover_finishes_an_interrupted_commit() {
+        let database_file = test_database("commit");
+        let index_file = parser::index_file(&database_file);
+        let dir = Path::new(&database_file).parent().unwrap();
+        let new_database = dir.join("database.new.bin").to_string_lossy().into_owned();
+        let new_index = dir.join("database.new.idx").to_string_lossy().into_owned();
+        let memory_trie = dir.join("name_memory_trie.bin").to_string_lossy().into_owned();
+        fs::write(&new_index, b"new index").unwrap();
+        fs::write(&memory_trie, b"old memory trie").unwrap();
+
+        // Interrupted after the database was renamed, before the index and the removal
+        let renames = [
+            (new_database.clone(), database_file.clone()),
+            (new_index.clone(), index_file.clone()),
+            (memory_trie.clone(), String::new()),
+        ];
+        let mut list = String::new();
+        for (from, to) in renames.iter() {
+            list.push_str(&format!("{}\n{}\n", from, to));
+        }
+        fs::write(commit_file(&database_file), list).unwrap();
+        fs::write(&database_file, b"new database").unwrap();
+        // A copy of the database which was being written is left out
+        fs::write(atomic_file::temp_file(&database_file), b"unfinished").unwrap();
+
+        assert!(needs_recovery(&database_file));
+        assert!(recover(&database_file).unwrap());
+        assert_eq!(fs::read(&database_file).unwrap(), b"new database");
+        assert_eq!(fs::read(&index_file).unwrap(), b"new index");
+        assert!(!Path::new(&new_index).exists());
+        assert!(!Path::new(&memory_trie).exists());
+        assert!(!Path::new(&atomic_file::temp_file(&database_file)).exists());
+        assert!(!needs_recovery(&database_file));
+
+        remove_database(&database_file);
+    }
+}
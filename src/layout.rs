@@ -0,0 +1,141 @@
+use category::Category;
+use period::Period;
+use record::{FIELD_NAMES, RECORD_SIZES};
+use std::{error::Error, fs};
+
+// Width, in bytes, of each field of the records of an old fixed-width database, which is found
+// from its size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub sizes: [usize; 22],
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            sizes: RECORD_SIZES,
+        }
+    }
+}
+
+// Quantity of fields of the records of the fixed-width databases: the first ones had 19 (323
+// bytes), and then the category, the period and the links were added at the end, one at a time
+const OLD_FIELD_COUNTS: [usize; 4] = [19, 20, 21, 22];
+
+impl Layout {
+    // The default widths of the first `fields` fields, without the ones added after them
+    pub fn with_fields(fields: usize) -> Layout {
+        let mut layout = Layout::default();
+        for size in layout.sizes[fields..].iter_mut() {
+            *size = 0;
+        }
+        layout
+    }
+
+    // The layout whose records fit exactly in the database. When the size fits more than one of
+    // them, the records are read with each one, keeping the one where they make sense
+    pub fn load(database_file: &str) -> Result<Layout, Box<dyn Error>> {
+        let size = fs::metadata(database_file)?.len();
+        if size == 0 {
+            return Ok(Layout::default());
+        }
+
+        let mut layouts: Vec<Layout> = OLD_FIELD_COUNTS
+            .iter()
+            .map(|fields| Layout::with_fields(*fields))
+            .filter(|layout| size.is_multiple_of(layout.entry_size() as u64))
+            .collect();
+        if layouts.len() > 1 {
+            let bytes = fs::read(database_file)?;
+            layouts.retain(|layout| layout.fits(&bytes));
+        }
+
+        let entry_sizes: Vec<String> = OLD_FIELD_COUNTS
+            .iter()
+            .map(|fields| Layout::with_fields(*fields).entry_size().to_string())
+            .collect();
+        match layouts.len() {
+            1 => Ok(layouts[0]),
+            _ => Err(From::from(format!(
+                "The size of {} ({} bytes) doesn't match the records of a single old version ({} bytes)",
+                database_file,
+                size,
+                entry_sizes.join(", ")
+            ))),
+        }
+    }
+
+    // Whether every record read with this layout has a numeric ID, and a known category and a
+    // valid period when the layout has them. With a wrong one, they are read from the middle of
+    // the other fields
+    fn fits(&self, bytes: &[u8]) -> bool {
+        bytes.chunks(self.entry_size()).all(|record| {
+            let mut position = 0;
+            self.sizes.iter().enumerate().all(|(i, size)| {
+                let text = String::from_utf8_lossy(&record[position..position + size]);
+                let text = text.trim_matches(char::from(0)).trim();
+                position += size;
+
+                match FIELD_NAMES[i] {
+                    _ if *size == 0 => true,
+                    "ID" => text.parse::<u64>().is_ok(),
+                    "CATEGORIA" => Category::from_name(text).is_ok(),
+                    "PERIODO" => Period::parse(text).is_ok(),
+                    _ => true,
+                }
+            })
+        })
+    }
+
+    pub fn entry_size(&self) -> usize {
+        self.sizes.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    // Fixed-width records with the fields of the layout, filled with zeros
+    fn records(layout: &Layout, count: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for i in 0..count {
+            for (field, size) in FIELD_NAMES.iter().zip(layout.sizes.iter()) {
+                let mut text = match *field {
+                    "NOME" => format!("WORKER {}", i),
+                    "ID" => (1000 + i).to_string(),
+                    "CATEGORIA" => "Servidores".to_string(),
+                    "PERIODO" => "201807".to_string(),
+                    _ => String::new(),
+                }
+                .into_bytes();
+                text.resize(*size, 0);
+                bytes.extend(text);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn load_finds_the_layout_when_the_size_fits_more_than_one() {
+        let dir = env::temp_dir().join(format!("fwb_layout_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let database_file = dir.join("database.bin").to_string_lossy().into_owned();
+
+        // 644 records of 323 bytes, or 323 of 644 bytes
+        let oldest = Layout::with_fields(19);
+        let newest = Layout::with_fields(22);
+        fs::write(&database_file, records(&oldest, 644)).unwrap();
+        assert_eq!(Layout::load(&database_file).unwrap(), oldest);
+        fs::write(&database_file, records(&newest, 323)).unwrap();
+        assert_eq!(Layout::load(&database_file).unwrap(), newest);
+
+        fs::write(&database_file, records(&Layout::with_fields(20), 3)).unwrap();
+        assert_eq!(Layout::load(&database_file).unwrap(), Layout::with_fields(20));
+        fs::write(&database_file, vec![0; 1000]).unwrap();
+        assert!(Layout::load(&database_file).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    error::Error,
+    fs::{File, OpenOptions, TryLockError},
+    path::Path,
+};
+
+// Several processes may use the same data files. The ones which only read them hold a shared
+// lock on database.lock, and the ones which change them (loading the CSV files, generating the
+// tries, inserting, updating and deleting workers...) an exclusive one, so no file is read while
+// it is being written. The lock file is next to the database it protects, so the other databases
+// opened by diff have their own. The locks are advisory, and are released when the process ends
+pub const LOCK_FILE: &str = "database.lock";
+
+// Holds the lock until it is dropped
+pub struct DataLock {
+    _file: File,
+}
+
+fn open_lock_file(database_file: &str) -> Result<File, Box<dyn Error>> {
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(Path::new(database_file).with_file_name(LOCK_FILE))?)
+}
+
+impl DataLock {
+    // Waits while another process changes the data files
+    pub fn shared(database_file: &str) -> Result<DataLock, Box<dyn Error>> {
+        let file = open_lock_file(database_file)?;
+        match file.try_lock_shared() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                println!("The database is being rebuilt by another process, waiting for it to finish...");
+                file.lock_shared()?;
+            }
+            Err(TryLockError::Error(err)) => return Err(From::from(err)),
+        }
+
+        Ok(DataLock { _file: file })
+    }
+
+    // Waits while any other process uses the data files
+    pub fn exclusive(database_file: &str) -> Result<DataLock, Box<dyn Error>> {
+        let file = open_lock_file(database_file)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                println!("The database is being used by another process, waiting for it to finish...");
+                file.lock()?;
+            }
+            Err(TryLockError::Error(err)) => return Err(From::from(err)),
+        }
+
+        Ok(DataLock { _file: file })
+    }
+}
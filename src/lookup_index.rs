@@ -0,0 +1,99 @@
+use data_dir;
+use header::FileKind;
+use parser;
+use record;
+use record_view::MappedDatabase;
+use replaced;
+use sorted_index;
+use std::error::Error;
+
+// The records sorted by a hash of a field which identifies the worker, so a worker is found by it
+// without reading the database. Different values may have the same hash, so the records found are
+// checked before being returned
+pub struct LookupField {
+    pub label: &'static str,
+    pub index_file: &'static str,
+    pub record_index: usize,
+}
+
+impl LookupField {
+    pub fn index_path(&self) -> String {
+        data_dir::path(self.index_file)
+    }
+}
+
+pub const ID_INDEX: LookupField = LookupField {
+    label: "ID",
+    index_file: "id_index.bin",
+    record_index: record::ID,
+};
+
+pub const CPF_INDEX: LookupField = LookupField {
+    label: "CPF",
+    index_file: "cpf_index.bin",
+    record_index: record::CPF,
+};
+
+pub const LOOKUP_FIELDS: [LookupField; 2] = [ID_INDEX, CPF_INDEX];
+
+// Only the digits are compared, so the masked CPF (***.123.456-**) is found with or without its
+// mask, e.g. as 123.456 or 123456
+fn key(text: &str) -> String {
+    text.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+// FNV-1a of the key, which is enough to spread them, as the matches are checked anyway
+pub fn hash(text: &str) -> i64 {
+    let key = key(text);
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash as i64
+}
+
+pub fn new_from_database(field: &LookupField) -> Result<(), Box<dyn Error>> {
+    let database = MappedDatabase::open(&parser::database_file())?;
+    let mut entries: Vec<(i64, u32)> = Vec::new();
+    database.for_each(|entry, view| {
+        entries.push((hash(view.text(field.record_index)), entry));
+    })?;
+
+    sorted_index::save(&field.index_path(), FileKind::LookupIndex, &database.header, entries)
+}
+
+// Entries of the records with the value, including the ones inserted after the index was
+// generated and the replaced ones, which the index has with their old values
+pub fn search(field: &LookupField, value: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    let wanted = key(value);
+    if wanted.is_empty() {
+        return Err(From::from(format!(
+            "Invalid {} \"{}\", it has no digits",
+            field.label, value
+        )));
+    }
+
+    let wanted_hash = hash(&wanted);
+    let (mut candidates, indexed_count) = sorted_index::search(
+        &field.index_path(),
+        FileKind::LookupIndex,
+        field.label,
+        wanted_hash,
+        wanted_hash,
+    )?;
+    candidates.extend(sorted_index::inserted_entries(indexed_count)?);
+    candidates.extend(replaced::entries(&parser::database_file())?);
+    candidates.sort();
+    candidates.dedup();
+
+    let mut entries: Vec<u32> = Vec::new();
+    for (entry, record) in parser::records_from_entries(candidates)? {
+        if key(&record.get(field.record_index)) == wanted {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
@@ -4,14 +4,46 @@ extern crate text_io;
 extern crate prettytable;
 extern crate clap;
 extern crate csv;
+extern crate encoding_rs;
+extern crate encoding_rs_io;
+extern crate crc32fast;
+extern crate flate2;
+extern crate memmap2;
+extern crate zip;
 
+mod atomic_file; // Import atomic_file.rs
+mod category; // Import category.rs
+mod check; // Import check.rs
 mod cli;    // Import cli.rs
+mod columns; // Import columns.rs
+mod compact; // Import compact.rs
+mod data_dir; // Import data_dir.rs
+mod date; // Import date.rs
+mod diff; // Import diff.rs
+mod encoding; // Import encoding.rs
+mod filter; // Import filter.rs
+mod header; // Import header.rs
+mod journal; // Import journal.rs
+mod layout; // Import layout.rs
+mod lock; // Import lock.rs
+mod lookup_index; // Import lookup_index.rs
+mod migrate; // Import migrate.rs
+mod money; // Import money.rs
 mod parser; // Import parser.rs
+mod period; // Import period.rs
 mod record; // Import record.rs
+mod record_view; // Import record_view.rs
+mod replaced; // Import replaced.rs
+mod rejects; // Import rejects.rs
+mod salary_index; // Import salary_index.rs
+mod sorted_index; // Import sorted_index.rs
+mod sorter; // Import sorter.rs
+mod source; // Import source.rs
 mod trie; // Import trie.rs
+mod vinculo; // Import vinculo.rs
 
 // Import used libraries
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use std::process;
 
 fn main() {
@@ -21,11 +53,58 @@ fn main() {
         .about("Search some data about the brazilian federal workers")
         .arg(
             Arg::with_name("csv")
-                .help("Sets the CSV files which will be used to generate the database")
+                .help("Sets the Remuneracao and Cadastro CSV files which will be used to generate the database (repeat it to load more categories)")
                 .takes_value(true)
                 .number_of_values(2)
+                .multiple(true)
                 .short("c")
                 .long("csv"),
+        ).arg(
+            Arg::with_name("zip")
+                .help("Sets the zip files downloaded from the Portal, with the Remuneracao and Cadastro CSV files, which will be used to generate the database")
+                .takes_value(true)
+                .multiple(true)
+                .long("zip"),
+        ).arg(
+            Arg::with_name("encoding")
+                .help("Sets the encoding of the CSV files, which are transcoded to UTF-8 while read")
+                .takes_value(true)
+                .possible_values(&encoding::ENCODING_NAMES)
+                .default_value("auto")
+                .global(true)
+                .long("encoding"),
+        ).arg(
+            Arg::with_name("columns")
+                .help("Sets a file with extra header names for the CSV columns, one COLUMN_KEY=Header name per line")
+                .takes_value(true)
+                .global(true)
+                .long("columns"),
+        ).arg(
+            Arg::with_name("csv_category")
+                .help("Sets the category of the CSV files, instead of detecting it from their names")
+                .takes_value(true)
+                .possible_values(&category::CATEGORY_NAMES)
+                .case_insensitive(true)
+                .global(true)
+                .long("csv-category"),
+        ).arg(
+            Arg::with_name("append")
+                .help("Adds the CSV files to the current database, instead of recreating it (e.g. to load another month)")
+                .global(true)
+                .long("append"),
+        ).arg(
+            Arg::with_name("data_dir")
+                .help("Sets the directory with the database, the tries and the indexes (the current one by default, or the data_dir=<directory> line of ~/.fwb.conf)")
+                .takes_value(true)
+                .env(data_dir::DATA_DIR_VARIABLE)
+                .global(true)
+                .long("data-dir"),
+        ).arg(
+            Arg::with_name("rejects")
+                .help("CSV file where the lines of the CSV files which couldn't be loaded are written (rejects.csv in the data directory by default)")
+                .takes_value(true)
+                .global(true)
+                .long("rejects"),
         ).arg(
             Arg::with_name("entry")
                 .help("Chooses the entry which will be searched in the database")
@@ -50,11 +129,87 @@ fn main() {
                 .takes_value(true)
                 .short("a")
                 .long("agency_name"),
+        ).arg(
+            Arg::with_name("category")
+                .help("Chooses the category of workers which will be searched in the database")
+                .takes_value(true)
+                .short("g")
+                .long("category"),
+        ).arg(
+            Arg::with_name("period")
+                .help("Chooses the period (YYYYMM) or the range of periods (YYYYMM:YYYYMM) which will be searched in the database")
+                .takes_value(true)
+                .short("m")
+                .long("period"),
+        ).arg(
+            Arg::with_name("id")
+                .help("Chooses the ID of the worker in the Portal (Id_SERVIDOR_PORTAL) which will be searched in the database")
+                .takes_value(true)
+                .long("id"),
+        ).arg(
+            Arg::with_name("cpf")
+                .help("Chooses the CPF of the worker which will be searched in the database, with or without its mask (***.123.456-**)")
+                .takes_value(true)
+                .long("cpf"),
+        ).arg(
+            Arg::with_name("min_salary")
+                .help("Chooses the lowest gross salary (e.g. 10000,00) of the workers which will be searched in the database")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .long("min-salary"),
+        ).arg(
+            Arg::with_name("max_salary")
+                .help("Chooses the highest gross salary of the workers which will be searched in the database")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .long("max-salary"),
+        ).arg(
+            Arg::with_name("min_net_salary")
+                .help("Chooses the lowest net salary (after the deductions) of the workers which will be searched in the database")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .long("min-net-salary"),
+        ).arg(
+            Arg::with_name("max_net_salary")
+                .help("Chooses the highest net salary (after the deductions) of the workers which will be searched in the database")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .long("max-net-salary"),
+        ).arg(
+            Arg::with_name("admission_year")
+                .help("Chooses the year (YYYY) or the range of years (YYYY:YYYY) of admission of the workers which will be searched in the database")
+                .takes_value(true)
+                .long("admission-year"),
+        ).arg(
+            Arg::with_name("min_tenure")
+                .help("Chooses the workers admitted at least this many years before the end of the period of their record")
+                .takes_value(true)
+                .long("min-tenure"),
+        ).arg(
+            Arg::with_name("max_tenure")
+                .help("Chooses the workers admitted at most this many years before the end of the period of their record")
+                .takes_value(true)
+                .long("max-tenure"),
+        ).arg(
+            Arg::with_name("on_leave")
+                .help("Chooses the workers who were on leave on the date (DD/MM/YYYY, or today)")
+                .takes_value(true)
+                .long("on-leave"),
         ).arg(
             Arg::with_name("new")
                 .short("n")
                 .multiple(true)
                 .help("Asks to insert a new entry in the database"),
+        ).arg(
+            Arg::with_name("update")
+                .help("Asks for the new values of the fields of an entry of the database")
+                .takes_value(true)
+                .long("update"),
+        ).arg(
+            Arg::with_name("delete")
+                .help("Deletes an entry of the database")
+                .takes_value(true)
+                .long("delete"),
         ).arg(
             Arg::with_name("trie")
                 .short("t")
@@ -72,10 +227,128 @@ fn main() {
             Arg::with_name("or_search")
                 .short("o")
                 .help("Runs the program CLI searches using OR instead of AND searches"),
+        ).subcommand(
+            SubCommand::with_name("prepare")
+                .about("Sorts the raw Portal da Transparencia files and generates the database")
+                .arg(
+                    Arg::with_name("remuneracao")
+                        .help("Raw Remuneracao CSV file, as downloaded from the Portal, or the zip file with both of them")
+                        .required(true)
+                        .index(1),
+                ).arg(
+                    Arg::with_name("cadastro")
+                        .help("Raw Cadastro CSV file, as downloaded from the Portal (not needed with a zip file)")
+                        .index(2),
+                ).arg(
+                    Arg::with_name("output")
+                        .help("Where the sorted Remuneracao file will be written (defaults to <remuneracao>Parsed.csv)")
+                        .takes_value(true)
+                        .short("o")
+                        .long("output"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("diff")
+                .about("Lists the workers who appeared, disappeared or changed between two periods")
+                .arg(
+                    Arg::with_name("from")
+                        .help("Period (YYYYMM) compared against, may be left out if its database has only one")
+                        .takes_value(true)
+                        .long("from"),
+                ).arg(
+                    Arg::with_name("to")
+                        .help("Period (YYYYMM) compared with the first one, may be left out if its database has only one")
+                        .takes_value(true)
+                        .long("to"),
+                ).arg(
+                    Arg::with_name("from_database")
+                        .help("Database file with the first period (the one in the data directory by default)")
+                        .takes_value(true)
+                        .long("from-database"),
+                ).arg(
+                    Arg::with_name("to_database")
+                        .help("Database file with the second period (the one in the data directory by default)")
+                        .takes_value(true)
+                        .long("to-database"),
+                ).arg(
+                    Arg::with_name("threshold")
+                        .help("Smallest change of the gross salary reported, as a percentage of the first one")
+                        .takes_value(true)
+                        .default_value("10")
+                        .long("threshold"),
+                ).arg(
+                    Arg::with_name("output")
+                        .help("CSV file where the differences will also be written")
+                        .takes_value(true)
+                        .short("o")
+                        .long("output"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("compact")
+                .about("Removes the deleted and the old versions of the updated records from the database, numbering the entries again"),
+        ).subcommand(
+            SubCommand::with_name("check")
+                .about("Checks the database, the tries and the indexes for damage"),
+        ).subcommand(
+            SubCommand::with_name("migrate")
+                .about("Converts the database and the tries written by an older version of the program"),
         ).get_matches();
 
     let prefix_search: bool = matches.occurrences_of("prefix_search") > 0;
     let or_search: bool = matches.occurrences_of("or_search") > 0;
+    let append: bool = matches.occurrences_of("append") > 0;
+
+    let csv_encoding = match encoding::Encoding::from_name(matches.value_of("encoding").unwrap()) {
+        Ok(csv_encoding) => csv_encoding,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    let aliases = match matches.value_of("columns") {
+        Some(alias_file) => match columns::Aliases::from_file(alias_file) {
+            Ok(aliases) => aliases,
+            Err(err) => {
+                println!("Error reading the column aliases: {}", err);
+                process::exit(1);
+            }
+        },
+        None => columns::Aliases::default(),
+    };
+    let csv_category = match matches.value_of("csv_category") {
+        Some(name) => match category::Category::from_name(name) {
+            Ok(csv_category) => Some(csv_category),
+            Err(err) => {
+                println!("{}", err);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let csv_options = parser::CsvOptions {
+        salary_encoding: csv_encoding,
+        info_encoding: csv_encoding,
+        aliases,
+    };
+
+    // Every file generated by the program goes to the data directory
+    let data_dir = match matches.value_of("data_dir") {
+        Some(dir) => Some(dir.to_string()),
+        None => match data_dir::from_config_file() {
+            Ok(dir) => dir,
+            Err(err) => {
+                println!("Error reading the configuration file: {}", err);
+                process::exit(1);
+            }
+        },
+    };
+    if let Some(dir) = data_dir {
+        data_dir::set(&dir);
+    }
+
+    let mut rejects = rejects::Rejects::new(&match matches.value_of("rejects") {
+        Some(rejects_file) => rejects_file.to_string(),
+        None => data_dir::path(rejects::DEFAULT_REJECTS_FILE),
+    });
 
     // Check if we should go to the interactive mode
     if matches.occurrences_of("interactive") > 0 {
@@ -86,12 +359,104 @@ fn main() {
         process::exit(0);
     }
 
+    // The commands which change the data files wait for the other processes using them, and
+    // the searches only wait for the ones changing them
+    let changes_data_files = ["prepare", "compact", "migrate"]
+        .iter()
+        .any(|command| matches.subcommand_matches(command).is_some())
+        || ["csv", "zip", "trie", "new", "update", "delete"]
+            .iter()
+            .any(|argument| matches.occurrences_of(argument) > 0);
+    if let Err(err) = cli::check_data_dir(changes_data_files) {
+        println!("{}", err);
+        process::exit(1);
+    }
+    let _lock = match cli::lock_data_files(changes_data_files) {
+        Ok(lock) => lock,
+        Err(err) => {
+            println!("Error locking the data files: {}", err);
+            process::exit(1);
+        }
+    };
+
+    // Sort the raw files, then parse them and build the tries
+    if let Some(prepare_matches) = matches.subcommand_matches("prepare") {
+        if let Err(err) =
+            cli::prepare_csv_files(prepare_matches, csv_category, csv_options, append, &mut rejects)
+        {
+            println!("\nError preparing the CSV files: {}", err);
+            process::exit(1);
+        }
+        if let Err(err) = rejects.print_summary() {
+            println!("Error writing the rejected lines: {}", err);
+            process::exit(1);
+        }
+
+        if let Err(err) = cli::reparse_tries() {
+            println!("Error reparsing the tries: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Compare two periods
+    if let Some(diff_matches) = matches.subcommand_matches("diff") {
+        if let Err(err) = cli::diff_databases(diff_matches) {
+            println!("Error comparing the periods: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Rewrite the database without the deleted records
+    if matches.subcommand_matches("compact").is_some() {
+        if let Err(err) = cli::check_data_files().and_then(|_| cli::compact_data_files()) {
+            println!("Error compacting the database: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Look for damaged files
+    if matches.subcommand_matches("check").is_some() {
+        match cli::verify_data_files() {
+            Ok(true) => process::exit(0),
+            Ok(false) => process::exit(1),
+            Err(err) => {
+                println!("Error checking the data files: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Convert the files of an older version
+    if matches.subcommand_matches("migrate").is_some() {
+        if let Err(err) = cli::migrate_data_files() {
+            println!("Error migrating the database: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     // Parse CSV and build tries
-    if let Some(csv_files) = matches.values_of("csv") {
-        if let Err(err) = cli::parse_csv_files(csv_files) {
+    let csv_files = match cli::csv_pairs(&matches) {
+        Ok(csv_files) => csv_files,
+        Err(err) => {
+            println!("Error opening the zip file: {}", err);
+            process::exit(1);
+        }
+    };
+    if !csv_files.is_empty() {
+        if let Err(err) =
+            cli::parse_csv_files(csv_files, csv_category, &csv_options, append, &mut rejects)
+        {
             println!("Error parsing the CSV {}", err);
             process::exit(1);
         }
+        if let Err(err) = rejects.print_summary() {
+            println!("Error writing the rejected lines: {}", err);
+            process::exit(1);
+        }
 
         if let Err(err) = cli::reparse_tries() {
             println!("Error reparsing the tries: {}", err);
@@ -107,41 +472,39 @@ fn main() {
         }
     }
 
-    // Instantiate the in-memory tries
-    let mut name_memory_trie: trie::Trie =
-        if let Ok(new_trie) = trie::Trie::new_from_file("name_memory_trie.bin".to_string()) {
-            new_trie
-        } else {
-            trie::Trie::new()
-        };
-    let mut role_memory_trie: trie::Trie =
-        if let Ok(new_trie) = trie::Trie::new_from_file("role_memory_trie.bin".to_string()) {
-            new_trie
-        } else {
-            trie::Trie::new()
-        };
-    let mut agency_memory_trie: trie::Trie =
-        if let Ok(new_trie) = trie::Trie::new_from_file("agency_memory_trie.bin".to_string()) {
-            new_trie
-        } else {
-            trie::Trie::new()
-        };
+    // Stop before reading files from another version
+    if let Err(err) = cli::check_data_files() {
+        println!("{}", err);
+        process::exit(1);
+    }
 
     // Create a new record in the database
     if matches.occurrences_of("new") > 0 {
-        if let Err(err) = cli::create_new_entry(
-            &mut name_memory_trie,
-            &mut role_memory_trie,
-            &mut agency_memory_trie,
-        ) {
+        if let Err(err) = cli::create_new_entry() {
             println!("Error creating a new entry in the database: {}", err);
             process::exit(1);
         }
     }
 
+    // Change or remove an entry of the database
+    if let Some(entry) = matches.value_of("update") {
+        if let Err(err) = cli::parse_entry(entry).and_then(cli::update_entry) {
+            println!("Error updating the entry: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+    if let Some(entry) = matches.value_of("delete") {
+        if let Err(err) = cli::parse_entry(entry).and_then(cli::delete_entry) {
+            println!("Error deleting the entry: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     // Search values in the database
     if let Err(err) = cli::search_on_database(matches, prefix_search, or_search) {
-        println!("Error creating a new entry in the database: {}", err);
+        println!("Error searching the database: {}", err);
         process::exit(1);
     }
 }
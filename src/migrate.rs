@@ -0,0 +1,254 @@
+use category;
+use header::{FileKind, Header, DATABASE_VERSION, HEADER_SIZE, TRIE_VERSION};
+use layout::Layout;
+use money;
+use parser::{self, DatabaseWriter};
+use record::{self, Record, FIELD_NAMES};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+use trie::{self, Trie};
+
+// Version of the format of a file, detected from its header. The files without one were written
+// before the headers existed: the databases with an index are from version 2, the other ones
+// from version 1
+fn file_version(file: &str, kind: FileKind) -> Result<u16, Box<dyn Error>> {
+    let mut start = [0; 6];
+    let mut reader = File::open(file)?;
+    match reader.read_exact(&mut start) {
+        Ok(()) if &start[0..4] == kind.magic() => return Ok(u16::from_le_bytes([start[4], start[5]])),
+        Ok(()) => {}
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+        Err(err) => return Err(From::from(err)),
+    }
+
+    if kind == FileKind::Database && Path::new(&parser::index_file(file)).exists() {
+        Ok(2)
+    } else {
+        Ok(1)
+    }
+}
+
+// Builds a Record from its fixed-size representation in a version 1 database. Some of them were
+// written before the fields were cut on character boundaries, and may have half a character, and
+// the oldest ones don't have the fields added later, which are left empty
+fn record_from_bytes(buffer: &[u8], layout: &Layout) -> Record {
+    let mut record = Record::default();
+    let mut position: usize = 0;
+
+    for (i, bytes) in layout.sizes.iter().enumerate() {
+        let text = String::from_utf8_lossy(&buffer[position..position + bytes]);
+
+        record.set(i, text.trim_matches(char::from(0)));
+        position += bytes;
+    }
+
+    // Only the Servidores were loaded before the category was kept
+    let category_field = record::field_index("CATEGORIA");
+    if layout.sizes[category_field] == 0 {
+        record.set(category_field, category::CATEGORY_NAMES[0]);
+    }
+
+    record
+}
+
+// Reads a record of a version 2, 3 or 4 database, where every field was text with its length
+// before it, except for the money of the version 4, in cents. The text is parsed again when it's
+// set, so the money and the dates get their current types
+fn read_text_record<R: Read>(reader: &mut R, version: u16) -> io::Result<Option<Record>> {
+    let mut record = Record::default();
+    let mut length = [0; 4];
+    let mut cents = [0; 8];
+
+    for i in 0..FIELD_NAMES.len() {
+        if version >= 4 && record::is_money_field(i) {
+            reader.read_exact(&mut cents)?;
+            record.set(i, &money::format(i64::from_le_bytes(cents)));
+            continue;
+        }
+
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(ref err) if i == 0 && err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        let mut field = vec![0; u32::from_le_bytes(length) as usize];
+        reader.read_exact(&mut field)?;
+        record.set(i, &String::from_utf8_lossy(&field));
+    }
+
+    Ok(Some(record))
+}
+
+// Rewrites the records of a database from an older version in the current format. Returns false
+// when it is already in it
+pub fn migrate_database(database_file: &str) -> Result<bool, Box<dyn Error>> {
+    let version = file_version(database_file, FileKind::Database)?;
+    if version == DATABASE_VERSION {
+        return Ok(false);
+    } else if version > DATABASE_VERSION {
+        // Gives the error about the newer version
+        Header::read_file(database_file, FileKind::Database)?;
+    }
+
+    // The widths of the version 1 are found before anything is written
+    let layout = if version == 1 {
+        Some(Layout::load(database_file)?)
+    } else {
+        None
+    };
+
+    println!(
+        "Converting the database {} from the version {} to the version {}",
+        database_file, version, DATABASE_VERSION
+    );
+
+    // The new database replaces the old one when it is finished
+    let mut writer = DatabaseWriter::create(database_file)?;
+    let mut reader = BufReader::new(File::open(database_file)?);
+    let mut records_count: u64 = 0;
+
+    if let Some(layout) = layout {
+        let mut buffer: Vec<u8> = vec![0; layout.entry_size()];
+        loop {
+            match reader.read_exact(&mut buffer) {
+                Ok(()) => writer.write(&record_from_bytes(&buffer, &layout))?,
+                Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(From::from(err)),
+            }
+            records_count += 1;
+        }
+    } else {
+        // The versions from 3 on have a header, whose sources are kept
+        if version >= 3 {
+            let header = Header::read_any_version(&mut reader, database_file, FileKind::Database)?;
+            for source in header.sources.iter() {
+                writer.add_source(source);
+            }
+            reader.seek(SeekFrom::Start(HEADER_SIZE))?;
+        }
+        while let Some(record) = read_text_record(&mut reader, version)? {
+            writer.write(&record)?;
+            records_count += 1;
+        }
+    }
+    drop(reader);
+    writer.finish()?;
+
+    println!("{} records were converted", records_count);
+    Ok(true)
+}
+
+// The memory tries can't be rebuilt from the database like the other ones, as they only have the
+// entries inserted after them, so their nodes are read and saved again with a header
+pub fn migrate_memory_tries() -> Result<(), Box<dyn Error>> {
+    for field in trie::INDEXED_FIELDS.iter() {
+        let memory_trie_file = &field.memory_trie_path();
+        if !Path::new(memory_trie_file).exists() {
+            continue;
+        }
+
+        let version = file_version(memory_trie_file, FileKind::Trie)?;
+        if version == TRIE_VERSION {
+            continue;
+        } else if version > TRIE_VERSION {
+            Header::read_file(memory_trie_file, FileKind::Trie)?;
+        }
+
+        println!(
+            "Converting the trie {} from the version {} to the version {}",
+            memory_trie_file, version, TRIE_VERSION
+        );
+        // The version 1 didn't have a header, the version 2 had 1 byte for each character and the
+        // version 3 had smaller quantities of values and children, which read_nodes knows
+        let mut reader = BufReader::new(File::open(memory_trie_file)?);
+        let header = if version >= 2 {
+            Some(Header::read_any_version(&mut reader, memory_trie_file, FileKind::Trie)?)
+        } else {
+            None
+        };
+        let mut trie = Trie::from_nodes(trie::read_nodes(&mut reader, version)?);
+        if let Some(header) = header {
+            trie.header.records_count = header.records_count;
+            trie.header.sources = header.sources;
+            trie.header.sources_count = header.sources_count;
+        }
+        drop(reader);
+        trie.save_to_file(memory_trie_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use record_view::MappedDatabase;
+    use std::{env, fs, process};
+
+    // A record of the first fixed-width databases, with its 19 fields filled with zeros
+    fn v1_record(fields: &[&str]) -> Vec<u8> {
+        let layout = Layout::with_fields(19);
+        let mut bytes: Vec<u8> = Vec::new();
+        for (i, size) in layout.sizes.iter().take(19).enumerate() {
+            let mut field = fields.get(i).unwrap_or(&"").as_bytes().to_vec();
+            field.resize(*size, 0);
+            bytes.extend(field);
+        }
+        bytes
+    }
+
+    #[test]
+    fn migrates_a_database_with_19_fields() {
+        let dir = env::temp_dir().join(format!("fwb_migrate_v1_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let database_file = dir.join("database.bin").to_string_lossy().into_owned();
+
+        let mut bytes = v1_record(&[
+            "JOSÉ DA SILVA",
+            "1234567",
+            "***.123.456-**",
+            "PROFESSOR DO MAGISTERIO SUPERIOR",
+            "UNIVERSIDADE FEDERAL DO RIO GRANDE DO SUL",
+            "12.345,67",
+        ]);
+        let mut second = v1_record(&["MARIA SOUZA", "7654321"]);
+        // The date a worker joined the agency is the last field
+        let date_start = Layout::with_fields(18).entry_size();
+        second[date_start..date_start + 10].copy_from_slice(b"01/02/2010");
+        bytes.extend(second);
+        assert_eq!(bytes.len(), 2 * 323);
+        fs::write(&database_file, &bytes).unwrap();
+
+        assert!(migrate_database(&database_file).unwrap());
+        assert!(!migrate_database(&database_file).unwrap());
+
+        let database = MappedDatabase::open(&database_file).unwrap();
+        assert_eq!(database.header.records_count, 2);
+        let first = database.get(1).unwrap().unwrap().to_record();
+        assert_eq!(first.get(record::field_index("NOME")), "JOSÉ DA SILVA");
+        assert_eq!(
+            first.get(record::field_index("ORGAO_EXERCICIO")),
+            "UNIVERSIDADE FEDERAL DO RIO GRANDE DO SUL"
+        );
+        assert_eq!(first.get_money(record::field_index("REMUNERACAO_BASICA_BRUTA")), Some(1234567));
+        assert_eq!(first.get(record::field_index("CATEGORIA")), category::CATEGORY_NAMES[0]);
+        assert_eq!(first.get(record::field_index("PERIODO")), "");
+
+        let second = database.get(2).unwrap().unwrap().to_record();
+        assert_eq!(second.get(record::field_index("ID")), "7654321");
+        let mut expected = Record::default();
+        expected.set(record::field_index("DATA_INGRESSO_ORGAO"), "01/02/2010");
+        assert!(expected.get_date(record::field_index("DATA_INGRESSO_ORGAO")).is_some());
+        assert_eq!(
+            second.get_date(record::field_index("DATA_INGRESSO_ORGAO")),
+            expected.get_date(record::field_index("DATA_INGRESSO_ORGAO"))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
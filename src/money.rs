@@ -0,0 +1,110 @@
+// The money values are kept in cents, so they can be sorted, compared and summed without the
+// rounding errors of floating point numbers
+
+// The values come from the Portal with a decimal comma and, sometimes, with dots between the
+// thousands (e.g. 27674,05, 27.674,05 or -1.234,5). Values with a single dot followed by one or
+// two digits, written by other tools, use it as the decimal point. Empty values are zero
+pub fn parse(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0);
+    }
+
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text.trim_start()),
+        None => (false, text),
+    };
+    let (integer, decimals) = match text.rfind(',') {
+        Some(position) => (&text[..position], &text[position + 1..]),
+        None => match text.rfind('.') {
+            Some(position) if text.matches('.').count() == 1 && text.len() - position <= 3 => {
+                (&text[..position], &text[position + 1..])
+            }
+            _ => (text, ""),
+        },
+    };
+
+    if decimals.len() > 2 || !decimals.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    // The dots between the thousands must separate groups of three digits
+    let groups: Vec<&str> = integer.split('.').collect();
+    for (i, group) in groups.iter().enumerate() {
+        let valid_size = if groups.len() == 1 {
+            true
+        } else if i == 0 {
+            !group.is_empty() && group.len() <= 3
+        } else {
+            group.len() == 3
+        };
+        if !valid_size || !group.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    }
+    if integer.is_empty() && decimals.is_empty() {
+        return None;
+    }
+
+    let mut cents: i64 = 0;
+    for digit in groups.concat().chars().chain(format!("{:0<2}", decimals).chars()) {
+        cents = cents
+            .checked_mul(10)?
+            .checked_add(i64::from(digit.to_digit(10)?))?;
+    }
+
+    Some(if negative { -cents } else { cents })
+}
+
+// Formats the cents like the Portal does, e.g. 2767405 as 27674,05
+pub fn format(cents: i64) -> String {
+    format!(
+        "{}{},{:02}",
+        if cents < 0 { "-" } else { "" },
+        cents.unsigned_abs() / 100,
+        cents.unsigned_abs() % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_the_values() {
+        let values = [
+            ("27674,05", 2767405),
+            ("27.674,05", 2767405),
+            ("-1.234,5", -123450),
+            ("1234567,89", 123456789),
+            ("0,07", 7),
+            ("12.5", 1250),
+            ("", 0),
+        ];
+        for (text, cents) in values.iter() {
+            assert_eq!(parse(text), Some(*cents), "{}", text);
+            assert_eq!(parse(&format(*cents)), Some(*cents));
+        }
+        assert_eq!(format(2767405), "27674,05");
+        assert_eq!(format(-7), "-0,07");
+        assert_eq!(format(i64::MIN), "-92233720368547758,08");
+        assert_eq!(parse(&format(i64::MAX)), Some(i64::MAX));
+    }
+
+    #[test]
+    fn rejects_the_invalid_values() {
+        let texts = [
+            "abc",
+            "1,234",
+            "12,3a",
+            "1.23.4,00",
+            "12.34.567",
+            "-",
+            ",",
+            "99999999999999999999",
+        ];
+        for text in texts.iter() {
+            assert_eq!(parse(text), None, "{}", text);
+        }
+    }
+}
@@ -1,161 +1,717 @@
-use csv::{ReaderBuilder, StringRecord};
-use record;
+use atomic_file;
+use category::Category;
+use columns::{self, Aliases, Column, ColumnMap};
+use crc32fast::Hasher;
+use csv::{self, ReaderBuilder, StringRecord};
+use data_dir;
+use date::{self, Date};
+use encoding;
+use encoding::Encoding;
+use header::{FileKind, Header, HEADER_SIZE};
+use journal::{self, Journal};
+use money;
+use period::Period;
 use record::Record;
+use record_view::MappedDatabase;
+use rejects::{Reason, Rejects};
+use replaced;
+use source::CsvSource;
+use vinculo::Vinculo;
 use std::{
+    collections::{hash_map::Entry, HashMap},
     error::Error,
-    fs,
-    fs::File,
-    io::{self, Read, Seek, SeekFrom, Write},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
     str,
 };
 
 pub const DATABASE_FILE: &str = "database.bin";
 
-pub fn generate_database_files(salary_file: &str, info_file: &str) -> Result<(), Box<Error>> {
+// The database in the data directory
+pub fn database_file() -> String {
+    data_dir::path(DATABASE_FILE)
+}
+pub const SEM_INFORMACAO: &str = "Sem informação";
+
+// Set in the offset of the entries of database.idx which were deleted
+pub const DELETED_ENTRY: u64 = 1 << 63;
+
+// Quantity of unmatched IDs printed in the join report for each of the files
+const REPORTED_UNMATCHED_IDS: usize = 10;
+
+// How the Remuneracao and Cadastro files should be read
+pub struct CsvOptions {
+    pub salary_encoding: Encoding,
+    pub info_encoding: Encoding,
+    pub aliases: Aliases,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            salary_encoding: Encoding::Auto,
+            info_encoding: Encoding::Auto,
+            aliases: Aliases::default(),
+        }
+    }
+}
+
+// Fields of a Cadastro row which end up in the Record
+#[derive(Default)]
+struct InfoValue {
+    descricao_cargo: String,
+    orgao_exercicio: String,
+    data_inicio_afastamento: Option<Date>,
+    data_termino_afastamento: Option<Date>,
+    jornada_trabalho: String,
+    data_ingresso_cargo: Option<Date>,
+    data_ingresso_orgao: Option<Date>,
+    vinculos: Vec<Vinculo>,
+    matched: bool,
+}
+
+impl InfoValue {
+    fn from_record(info_value: &StringRecord, columns: &ColumnMap) -> InfoValue {
+        let vinculo = vinculo_from_record(info_value, columns);
+        InfoValue {
+            descricao_cargo: columns.get(info_value, Column::DescricaoCargo).to_string(),
+            orgao_exercicio: columns.get(info_value, Column::OrgaoExercicio).to_string(),
+            data_inicio_afastamento: Date::from_field(
+                columns.get(info_value, Column::DataInicioAfastamento),
+            ),
+            data_termino_afastamento: Date::from_field(
+                columns.get(info_value, Column::DataTerminoAfastamento),
+            ),
+            jornada_trabalho: columns.get(info_value, Column::JornadaTrabalho).to_string(),
+            data_ingresso_cargo: Date::from_field(columns.get(info_value, Column::DataIngressoCargo)),
+            data_ingresso_orgao: Date::from_field(columns.get(info_value, Column::DataIngressoOrgao)),
+            vinculos: if vinculo.is_empty() { Vec::new() } else { vec![vinculo] },
+            matched: false,
+        }
+    }
+
+    // The link of another row of the same ID, kept if it isn't a copy of one of the others
+    fn add_vinculo(&mut self, vinculo: Vinculo) {
+        if !vinculo.is_empty() && !self.vinculos.contains(&vinculo) {
+            self.vinculos.push(vinculo);
+        }
+    }
+
+    // Used for the Remuneracao rows which don't have a Cadastro row
+    fn sem_informacao() -> InfoValue {
+        InfoValue {
+            descricao_cargo: SEM_INFORMACAO.to_string(),
+            orgao_exercicio: SEM_INFORMACAO.to_string(),
+            ..InfoValue::default()
+        }
+    }
+}
+
+fn vinculo_from_record(info_value: &StringRecord, columns: &ColumnMap) -> Vinculo {
+    Vinculo::new(
+        columns.get(info_value, Column::DescricaoCargo),
+        columns.get(info_value, Column::Funcao),
+        columns.get(info_value, Column::UorgExercicio),
+        columns.get(info_value, Column::OrgaoExercicio),
+    )
+}
+
+// Counters printed after the join between the Remuneracao and Cadastro files
+#[derive(Default)]
+struct JoinReport {
+    matched: u32,
+    unmatched_salary: u32,
+    unmatched_info: u32,
+    unmatched_salary_ids: Vec<String>,
+    unmatched_info_ids: Vec<String>,
+    without_period: u32,
+    multiple_vinculos: u32,
+}
+
+impl JoinReport {
+    fn print(&self) {
+        println!(
+            "\n{} Remuneracao rows were joined with their Cadastro row",
+            self.matched
+        );
+        if self.unmatched_salary > 0 {
+            println!(
+                "{} Remuneracao rows have no Cadastro row and were saved with \"{}\" (e.g. IDs {})",
+                self.unmatched_salary,
+                SEM_INFORMACAO,
+                self.unmatched_salary_ids.join(", ")
+            );
+        }
+        if self.unmatched_info > 0 {
+            println!(
+                "{} Cadastro IDs have no Remuneracao row and were ignored (e.g. IDs {})",
+                self.unmatched_info,
+                self.unmatched_info_ids.join(", ")
+            );
+        }
+        if self.without_period > 0 {
+            println!(
+                "{} Remuneracao rows have no ANO/MES and were saved without a period",
+                self.without_period
+            );
+        }
+        if self.multiple_vinculos > 0 {
+            println!(
+                "{} workers have more than one link (vínculo)",
+                self.multiple_vinculos
+            );
+        }
+    }
+}
+
+// Some Cadastro files were saved by other tools with the "ção" mangled into replacement characters
+pub fn is_sem_informacao(text: &str) -> bool {
+    text == SEM_INFORMACAO || text == "Sem informa\u{FFFD}\u{FFFD}o"
+}
+
+pub fn line(row: &StringRecord) -> u64 {
+    row.position().map_or(0, |position| position.line())
+}
+
+// Checks a row read from one of the CSV files, sending it to the rejects when it can't be read or
+// has a wrong number of columns. Only the I/O errors, like a corrupted zip file, stop the load
+pub fn check_row(
+    row: csv::Result<StringRecord>,
+    columns_count: usize,
+    csv_file: &str,
+    rejects: &mut Rejects,
+) -> Result<Option<StringRecord>, Box<dyn Error>> {
+    let row = match row {
+        Ok(row) => row,
+        Err(err) => {
+            if let csv::ErrorKind::Io(_) = *err.kind() {
+                return Err(From::from(err));
+            }
+
+            let line = err.position().map_or(0, |position| position.line());
+            rejects.add(csv_file, line, Reason::InvalidRow, &err.to_string(), None)?;
+            return Ok(None);
+        }
+    };
+
+    if row.len() != columns_count {
+        rejects.add(
+            csv_file,
+            line(&row),
+            Reason::WrongColumnCount,
+            &format!("{} columns instead of {}", row.len(), columns_count),
+            Some(&row),
+        )?;
+        return Ok(None);
+    }
+
+    Ok(Some(row))
+}
+
+// The raw files end with some lines which aren't workers
+fn is_valid_id(id: &str) -> bool {
+    id.trim().parse::<u64>().is_ok()
+}
+
+// Indexes the Cadastro file by Id_SERVIDOR_PORTAL. When the same ID has more than one row, the
+// link of each row is kept, and the first row with a role is used as the main one, falling back
+// to the first row of that ID
+fn index_info_file(
+    info_file: &CsvSource,
+    category: Category,
+    options: &CsvOptions,
+    rejects: &mut Rejects,
+) -> Result<HashMap<String, InfoValue>, Box<dyn Error>> {
+    let info_file_name = info_file.name();
+    let mut csv_info_reader = ReaderBuilder::new()
+        .delimiter(b';')
+        .flexible(true)
+        .from_reader(encoding::open(info_file, options.info_encoding)?);
+    let columns = ColumnMap::from_headers(
+        csv_info_reader.headers()?,
+        &columns::INFO_COLUMNS,
+        category.required_info_columns(),
+        &options.aliases,
+        &info_file_name,
+    )?;
+    let columns_count = csv_info_reader.headers()?.len();
+    let mut info_values: HashMap<String, InfoValue> = HashMap::new();
+
+    for info_value in csv_info_reader.records() {
+        let info_value = match check_row(info_value, columns_count, &info_file_name, rejects)? {
+            Some(info_value) => info_value,
+            None => continue,
+        };
+
+        let id = columns.get(&info_value, Column::Id).to_string();
+        if !is_valid_id(&id) {
+            rejects.add(&info_file_name, line(&info_value), Reason::InvalidId, &id, Some(&info_value))?;
+            continue;
+        }
+
+        let invalid_date = columns::DATE_COLUMNS.iter().find(|column| {
+            let text = columns.get(&info_value, **column);
+            !date::is_missing(text) && Date::parse(text).is_err()
+        });
+        if let Some(column) = invalid_date {
+            rejects.add(
+                &info_file_name,
+                line(&info_value),
+                Reason::InvalidDate,
+                &format!("{}: \"{}\"", column.key(), columns.get(&info_value, *column)),
+                Some(&info_value),
+            )?;
+            continue;
+        }
+
+        match info_values.entry(id) {
+            Entry::Vacant(entry) => {
+                entry.insert(InfoValue::from_record(&info_value, &columns));
+            }
+            Entry::Occupied(mut entry) => {
+                let current = entry.get_mut();
+                if is_sem_informacao(&current.descricao_cargo)
+                    && !is_sem_informacao(columns.get(&info_value, Column::DescricaoCargo))
+                {
+                    // The new row becomes the main one, keeping the links found before it
+                    let vinculos = current.vinculos.split_off(0);
+                    *current = InfoValue::from_record(&info_value, &columns);
+                    for vinculo in vinculos {
+                        current.add_vinculo(vinculo);
+                    }
+                } else {
+                    current.add_vinculo(vinculo_from_record(&info_value, &columns));
+                }
+            }
+        }
+    }
+
+    Ok(info_values)
+}
+
+// The Remuneracao file may be the sorted one, separated by ',', or the raw one from the Portal,
+// separated by ';', so the separator is guessed from its header
+fn salary_delimiter(salary_file: &CsvSource, encoding: Encoding) -> Result<u8, Box<dyn Error>> {
+    let mut header = String::new();
+    BufReader::new(encoding::open(salary_file, encoding)?).read_line(&mut header)?;
+
+    if header.matches(';').count() > header.matches(',').count() {
+        Ok(b';')
+    } else {
+        Ok(b',')
+    }
+}
+
+// Every record is tagged with the given category and with its period, read from the ANO and MES
+// columns or, when the file doesn't have them, from the file name. The database is recreated,
+// unless `append` is set, when the records are added after the ones already in it. The lines
+// which can't be loaded, or are loaded with some field cut, are written to the rejects
+pub fn generate_database_files(
+    salary_file: &CsvSource,
+    info_file: &CsvSource,
+    category: Category,
+    options: &CsvOptions,
+    append: bool,
+    rejects: &mut Rejects,
+) -> Result<(), Box<dyn Error>> {
+    let salary_file_name = salary_file.name();
     let mut csv_salary_reader = ReaderBuilder::new()
-        .delimiter(b',')
-        .has_headers(false)
-        .from_path(salary_file)?;
-    let mut csv_info_reader = ReaderBuilder::new().delimiter(b';').from_path(info_file)?;
-    let mut output_file = File::create(DATABASE_FILE)?;
+        .delimiter(salary_delimiter(salary_file, options.salary_encoding)?)
+        .flexible(true)
+        .from_reader(encoding::open(salary_file, options.salary_encoding)?);
+    let columns = ColumnMap::from_headers(
+        csv_salary_reader.headers()?,
+        &columns::SALARY_COLUMNS,
+        category.required_salary_columns(),
+        &options.aliases,
+        &salary_file_name,
+    )?;
+    let columns_count = csv_salary_reader.headers()?.len();
+    let mut info_values = index_info_file(info_file, category, options, rejects)?;
+    let mut database = if append {
+        DatabaseWriter::append(&database_file())?
+    } else {
+        DatabaseWriter::create(&database_file())?
+    };
+    database.add_source(&salary_file_name);
+    database.add_source(&info_file.name());
+    let file_period = Period::detect(&salary_file_name);
     let mut counter: u32 = 0;
+    let mut report = JoinReport::default();
+    let missing_info_value = InfoValue::sem_informacao();
 
-    let salary_values = csv_salary_reader.records().map(|r| r.unwrap());
-    let mut info_values = csv_info_reader.records().map(|r| r.unwrap()).peekable();
-    let mut sem_informacao_vec: Vec<u8> = Vec::new();
-
-    for elem in vec![
-        83, 101, 109, 32, 105, 110, 102, 111, 114, 109, 97, 239, 191, 189, 239, 191, 189, 111,
-    ] {
-        sem_informacao_vec.push(elem);
-    }
-
-    for salary_value in salary_values {
-        let mut info_value: StringRecord = StringRecord::new();
-        while let Some(possible_info_value) = Some(info_values.next().unwrap()) {
-            if possible_info_value.get(0).unwrap().as_bytes().to_vec()
-                != salary_value[2].as_bytes().to_vec()
-            {
-                continue;
-            } else if possible_info_value.get(4).unwrap().as_bytes().to_vec() != sem_informacao_vec
-            {
-                info_value = possible_info_value; // Primeira entrada com aquele ID tem uma profissao
-                break;
-            } else if info_values
-                .peek()
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .as_bytes()
-                .to_vec()
-                == salary_value[2].as_bytes().to_vec()
-            {
-                // Ultima posição não tinha profissão, mas próxima vai ter, então pego a proxima
-                info_value = info_values.next().unwrap();
-                break;
-            } else {
-                //Se a pessoa não tem informação no db, eu uso "Sem informacao" mesmo
-                info_value = possible_info_value;
-                break;
-            }
+    for salary_value in csv_salary_reader.records() {
+        let salary_value =
+            match check_row(salary_value, columns_count, &salary_file_name, rejects)? {
+                Some(salary_value) => salary_value,
+                None => continue,
+            };
+
+        let id = columns.get(&salary_value, Column::Id);
+        if !is_valid_id(id) {
+            rejects.add(&salary_file_name, line(&salary_value), Reason::InvalidId, id, Some(&salary_value))?;
+            continue;
         }
 
+        let invalid_money = columns::MONEY_COLUMNS
+            .iter()
+            .find(|column| money::parse(columns.get(&salary_value, **column)).is_none());
+        if let Some(column) = invalid_money {
+            rejects.add(
+                &salary_file_name,
+                line(&salary_value),
+                Reason::InvalidMoney,
+                &format!("{}: \"{}\"", column.key(), columns.get(&salary_value, *column)),
+                Some(&salary_value),
+            )?;
+            continue;
+        }
+
+        let info_value: &InfoValue = match info_values.get_mut(id) {
+            Some(info_value) => {
+                info_value.matched = true;
+                report.matched += 1;
+                info_value
+            }
+            None => {
+                report.unmatched_salary += 1;
+                if report.unmatched_salary_ids.len() < REPORTED_UNMATCHED_IDS {
+                    report.unmatched_salary_ids.push(id.to_string());
+                }
+                &missing_info_value
+            }
+        };
+
+        let period = Period::from_columns(
+            columns.get(&salary_value, Column::Ano),
+            columns.get(&salary_value, Column::Mes),
+        ).or(file_period);
+        let periodo = match period {
+            Some(period) => period.to_string().as_bytes().to_vec(),
+            None => {
+                report.without_period += 1;
+                Vec::new()
+            }
+        };
+
+        // Already checked above
+        let money = |column: Column| money::parse(columns.get(&salary_value, column)).unwrap_or(0);
         let mut record = Record {
-            id: salary_value[2].as_bytes().to_vec(),
-            cpf: salary_value[3].as_bytes().to_vec(),
-            nome: salary_value[4].as_bytes().to_vec(),
-            descricao_cargo: info_value[4].as_bytes().to_vec(),
-            orgao_exercicio: info_value[24].as_bytes().to_vec(),
-            remuneracao_basica_bruta_rs: salary_value[5].as_bytes().to_vec(),
-            gratificacao_natalina_rs: salary_value[9].as_bytes().to_vec(),
-            ferias_rs: salary_value[13].as_bytes().to_vec(),
-            outras_remuneracoes_eventuais_rs: salary_value[15].as_bytes().to_vec(),
-            irrf_rs: salary_value[17].as_bytes().to_vec(),
-            pss_rgps_rs: salary_value[19].as_bytes().to_vec(),
-            demais_deducoes_rs: salary_value[21].as_bytes().to_vec(),
-            remuneracao_apos_deducoes_obrigatorias_rs: salary_value[29].as_bytes().to_vec(),
-            total_verbas_indenizatorias_rs: salary_value[37].as_bytes().to_vec(),
-            data_inicio_afastamento: info_value[29].as_bytes().to_vec(),
-            data_termino_afastamento: info_value[30].as_bytes().to_vec(),
-            jornada_trabalho: info_value[32].as_bytes().to_vec(),
-            data_ingresso_cargo: info_value[33].as_bytes().to_vec(),
-            data_ingresso_orgao: info_value[35].as_bytes().to_vec(),
+            id: id.as_bytes().to_vec(),
+            cpf: columns.get(&salary_value, Column::Cpf).as_bytes().to_vec(),
+            nome: columns.get(&salary_value, Column::Nome).as_bytes().to_vec(),
+            descricao_cargo: info_value.descricao_cargo.as_bytes().to_vec(),
+            orgao_exercicio: info_value.orgao_exercicio.as_bytes().to_vec(),
+            remuneracao_basica_bruta_rs: money(Column::RemuneracaoBasicaBruta),
+            gratificacao_natalina_rs: money(Column::GratificacaoNatalina),
+            ferias_rs: money(Column::Ferias),
+            outras_remuneracoes_eventuais_rs: money(Column::OutrasRemuneracoesEventuais),
+            irrf_rs: money(Column::Irrf),
+            pss_rgps_rs: money(Column::PssRgps),
+            demais_deducoes_rs: money(Column::DemaisDeducoes),
+            remuneracao_apos_deducoes_obrigatorias_rs: money(Column::RemuneracaoAposDeducoes),
+            total_verbas_indenizatorias_rs: money(Column::TotalVerbasIndenizatorias),
+            data_inicio_afastamento: info_value.data_inicio_afastamento,
+            data_termino_afastamento: info_value.data_termino_afastamento,
+            jornada_trabalho: info_value.jornada_trabalho.as_bytes().to_vec(),
+            data_ingresso_cargo: info_value.data_ingresso_cargo,
+            data_ingresso_orgao: info_value.data_ingresso_orgao,
+            categoria: category.name().as_bytes().to_vec(),
+            periodo,
+            vinculos: Vec::new(),
         };
 
-        record.resize();
-        output_file.write(&record.as_u8_array()).unwrap();
+        if info_value.vinculos.len() > 1 {
+            report.multiple_vinculos += 1;
+        }
+        record.set_vinculos(&info_value.vinculos);
+        database.write(&record)?;
 
         counter += 1;
-        if counter % 40_000 == 0 {
+        if counter.is_multiple_of(40_000) {
             print!(".");
             io::stdout().flush().unwrap();
         }
     }
 
+    for (id, info_value) in info_values.iter() {
+        if !info_value.matched {
+            report.unmatched_info += 1;
+            if report.unmatched_info_ids.len() < REPORTED_UNMATCHED_IDS {
+                report.unmatched_info_ids.push(id.clone());
+            }
+        }
+    }
+    report.print();
+
+    database.finish()?;
+
     Ok(())
 }
 
-pub fn exceeds_database_size(entry_position: u64) -> bool {
-    let metadata = fs::metadata(DATABASE_FILE).unwrap();
-    if metadata.len() > entry_position {
-        false
-    } else {
-        true
+// The index of a database has the offset of each of its records, 8 bytes (little-endian) for
+// each, so the record of an entry is found without reading the ones before it
+pub fn index_file(database_file: &str) -> String {
+    Path::new(database_file)
+        .with_extension("idx")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Writes the records to the end of a database and of its index. The header, with the quantity
+// of records and the checksum, is only updated by `finish`. A new database is written to
+// temporary files, which replace the old one at the end, and the changes to an existing one are
+// saved in a journal first, so they are undone if the program is interrupted
+pub struct DatabaseWriter {
+    database_file: String,
+    database: BufWriter<File>,
+    index: BufWriter<File>,
+    header: Header,
+    hasher: Hasher,
+    offset: u64,
+    journal: Option<Journal>,
+}
+
+impl DatabaseWriter {
+    pub fn create(database_file: &str) -> Result<DatabaseWriter, Box<dyn Error>> {
+        let header = Header::new(FileKind::Database);
+        let mut database = BufWriter::new(atomic_file::create(database_file)?);
+        database.write_all(&header.to_bytes())?;
+
+        Ok(DatabaseWriter {
+            database_file: database_file.to_string(),
+            database,
+            index: BufWriter::new(atomic_file::create(&index_file(database_file))?),
+            header,
+            hasher: Hasher::new(),
+            offset: HEADER_SIZE,
+            journal: None,
+        })
+    }
+
+    pub fn append(database_file: &str) -> Result<DatabaseWriter, Box<dyn Error>> {
+        if !Path::new(database_file).exists() {
+            return DatabaseWriter::create(database_file);
+        }
+
+        let mut database = OpenOptions::new().read(true).write(true).open(database_file)?;
+        let header = Header::read(&mut database, database_file, FileKind::Database)?;
+        let index = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(index_file(database_file))?;
+        if index.metadata()?.len() / 8 != header.records_count {
+            return Err(From::from(format!(
+                "The index {} doesn't match the database {}, load the CSV files again without --append",
+                index_file(database_file),
+                database_file
+            )));
+        }
+        let offset = database.seek(SeekFrom::End(0))?;
+
+        Ok(DatabaseWriter {
+            database_file: database_file.to_string(),
+            database: BufWriter::new(database),
+            index: BufWriter::new(index),
+            hasher: Hasher::new_with_initial(header.checksum),
+            header,
+            offset,
+            journal: Some(Journal::begin(database_file)?),
+        })
+    }
+
+    pub fn add_source(&mut self, source: &str) {
+        self.header.add_source(source);
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let mut bytes: Vec<u8> = Vec::new();
+        record.write_to(&mut bytes)?;
+
+        self.index.write_all(&self.offset.to_le_bytes())?;
+        self.database.write_all(&bytes)?;
+        self.hasher.update(&bytes);
+        self.offset += bytes.len() as u64;
+        self.header.records_count += 1;
+        Ok(())
+    }
+
+    // Writes the new version of the record of an entry after the other records, pointing the entry
+    // to it. The old version is left where it is, and the tries and the indexes still have it, so
+    // the entry is listed as replaced
+    pub fn replace(&mut self, entry: u32, record: &Record) -> Result<(), Box<dyn Error>> {
+        if entry == 0 || u64::from(entry) > self.header.records_count {
+            return Err(From::from(format!(
+                "There is no entry {} in the database {}, which has {} entries",
+                entry, self.database_file, self.header.records_count
+            )));
+        }
+
+        let mut bytes: Vec<u8> = Vec::new();
+        record.write_to(&mut bytes)?;
+        replaced::add(&self.database_file, entry)?;
+        self.database.write_all(&bytes)?;
+        self.hasher.update(&bytes);
+
+        // The entries written by this writer are still in its buffer
+        self.index.flush()?;
+        let mut index = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(index_file(&self.database_file))?;
+        let mut old_offset = [0; 8];
+        index.seek(SeekFrom::Start(u64::from(entry - 1) * 8))?;
+        index.read_exact(&mut old_offset)?;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.save_entry(entry, u64::from_le_bytes(old_offset))?;
+        }
+        index.seek(SeekFrom::Start(u64::from(entry - 1) * 8))?;
+        index.write_all(&self.offset.to_le_bytes())?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.header.checksum = self.hasher.clone().finalize();
+        self.database.seek(SeekFrom::Start(0))?;
+        self.database.write_all(&self.header.to_bytes())?;
+        atomic_file::sync(self.index)?;
+        atomic_file::sync(self.database)?;
+
+        match self.journal {
+            Some(journal) => journal.commit()?,
+            None => {
+                let index_file = index_file(&self.database_file);
+                atomic_file::replace_all(
+                    &journal::commit_file(&self.database_file),
+                    &[
+                        (atomic_file::temp_file(&self.database_file), self.database_file.clone()),
+                        (atomic_file::temp_file(&index_file), index_file),
+                    ],
+                )?;
+            }
+        }
+
+        Ok(())
     }
 }
 
-pub fn records_from_entries(entries: Vec<u32>) -> Option<Vec<Record>> {
-    let mut f = File::open(DATABASE_FILE).unwrap();
-    let mut buffer: Vec<u8>;
-    let mut record: Record;
-    let mut returned_records: Vec<Record> = Vec::new();
+pub fn read_header(database_file: &str) -> Result<Header, Box<dyn Error>> {
+    Header::read_file(database_file, FileKind::Database)
+}
+
+// Quantity of records in a database, which are the entries 1 to it
+pub fn records_count(database_file: &str) -> Result<u64, Box<dyn Error>> {
+    Ok(read_header(database_file)?.records_count)
+}
 
-    let mut current_offset: i64 = f.seek(SeekFrom::Start(0)).unwrap() as i64;
+// The records of the entries, with their entries, leaving out the ones past the workers in the
+// database and the deleted ones
+pub fn records_from_entries(entries: Vec<u32>) -> Result<Vec<(u32, Record)>, Box<dyn Error>> {
+    let database = MappedDatabase::open(&database_file())?;
+    let mut returned_records: Vec<(u32, Record)> = Vec::new();
 
     for entry in entries {
-        current_offset = f
-            .seek(SeekFrom::Current(
-                ((entry - 1) * record::DATA_ENTRY_SIZE as u32) as i64 - current_offset,
-            )).unwrap() as i64;
-        record = Record::default();
-
-        if exceeds_database_size(current_offset as u64) {
-            continue; // Checks if there is that many workers in the database
-        }
-
-        for (i, bytes) in record::RECORD_SIZES.iter().enumerate() {
-            buffer = vec![0; *bytes as usize];
-            f.read_exact(&mut buffer).unwrap();
-
-            let text: &str = str::from_utf8(&buffer).unwrap().trim_matches(char::from(0));
-
-            match i {
-                0 => record.nome = text.as_bytes().to_vec(),
-                1 => record.id = text.as_bytes().to_vec(),
-                2 => record.cpf = text.as_bytes().to_vec(),
-                3 => record.descricao_cargo = text.as_bytes().to_vec(),
-                4 => record.orgao_exercicio = text.as_bytes().to_vec(),
-                5 => record.remuneracao_basica_bruta_rs = text.as_bytes().to_vec(),
-                6 => record.gratificacao_natalina_rs = text.as_bytes().to_vec(),
-                7 => record.ferias_rs = text.as_bytes().to_vec(),
-                8 => record.outras_remuneracoes_eventuais_rs = text.as_bytes().to_vec(),
-                9 => record.irrf_rs = text.as_bytes().to_vec(),
-                10 => record.pss_rgps_rs = text.as_bytes().to_vec(),
-                11 => record.demais_deducoes_rs = text.as_bytes().to_vec(),
-                12 => record.remuneracao_apos_deducoes_obrigatorias_rs = text.as_bytes().to_vec(),
-                13 => record.total_verbas_indenizatorias_rs = text.as_bytes().to_vec(),
-                14 => record.data_inicio_afastamento = text.as_bytes().to_vec(),
-                15 => record.data_termino_afastamento = text.as_bytes().to_vec(),
-                16 => record.jornada_trabalho = text.as_bytes().to_vec(),
-                17 => record.data_ingresso_cargo = text.as_bytes().to_vec(),
-                18 => record.data_ingresso_orgao = text.as_bytes().to_vec(),
-                _ => println!("Error!!"),
-            }
+        if let Some(view) = database.get(entry)? {
+            returned_records.push((entry, view.to_record()));
         }
+    }
+
+    Ok(returned_records)
+}
+
+// Reads the record of every entry of a database file, in order, without keeping them in memory.
+// The deleted entries are skipped
+pub fn for_each_record<F: FnMut(u32, Record)>(
+    database_file: &str,
+    mut function: F,
+) -> Result<(), Box<dyn Error>> {
+    MappedDatabase::open(database_file)?.for_each(|entry, view| function(entry, view.to_record()))
+}
+
+// Marks an entry as deleted in the index, so it isn't read anymore. Its record is only removed
+// from the database when it is compacted
+pub fn delete_entry(database_file: &str, entry: u32) -> Result<(), Box<dyn Error>> {
+    let records_count = records_count(database_file)?;
+    if entry == 0 || u64::from(entry) > records_count {
+        return Err(From::from(format!(
+            "There is no entry {} in the database {}, which has {} entries",
+            entry, database_file, records_count
+        )));
+    }
 
-        returned_records.push(record);
-        current_offset += record::DATA_ENTRY_SIZE as i64;
+    let mut index = OpenOptions::new().read(true).write(true).open(index_file(database_file))?;
+    let mut offset = [0; 8];
+    index.seek(SeekFrom::Start(u64::from(entry - 1) * 8))?;
+    index.read_exact(&mut offset)?;
+    let offset = u64::from_le_bytes(offset);
+    if offset & DELETED_ENTRY != 0 {
+        return Err(From::from(format!("The entry {} was already deleted", entry)));
     }
 
-    Some(returned_records)
+    let mut journal = Journal::begin(database_file)?;
+    journal.save_entry(entry, offset)?;
+    index.seek(SeekFrom::Start(u64::from(entry - 1) * 8))?;
+    index.write_all(&(offset | DELETED_ENTRY).to_le_bytes())?;
+    index.sync_all()?;
+    journal.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn joins_the_links_of_each_id() {
+        let dir = env::temp_dir().join(format!("fwb_parser_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let info_file = dir.join("201807_Cadastro.csv").to_string_lossy().into_owned();
+        let rejects_file = dir.join("rejects.csv").to_string_lossy().into_owned();
+
+        let dates = "Sem informação;Sem informação;40 HORAS SEMANAIS;01/02/2010;01/02/2010";
+        let rows = [
+            "Id_SERVIDOR_PORTAL;DESCRICAO_CARGO;FUNCAO;UORG_EXERCICIO;ORG_EXERCICIO;\
+             DATA_INICIO_AFASTAMENTO;DATA_TERMINO_AFASTAMENTO;JORNADA_DE_TRABALHO;\
+             DATA_INGRESSO_CARGOFUNCAO;DATA_INGRESSO_ORGAO"
+                .to_string(),
+            format!("100;Sem informação;DIRETOR;REITORIA;UFRGS;{}", dates),
+            format!("100;PROFESSOR;-;INSTITUTO DE INFORMATICA;UFRGS;{}", dates),
+            format!("100;PROFESSOR;-;INSTITUTO DE INFORMATICA;UFRGS;{}", dates),
+            format!("200;ANALISTA;Sem informação;DTI;UFSC;{}", dates),
+            format!("ABC;ANALISTA;-;DTI;UFSC;{}", dates),
+        ];
+        fs::write(&info_file, rows.join("\n")).unwrap();
+
+        let mut rejects = Rejects::new(&rejects_file);
+        let info_values = index_info_file(
+            &CsvSource::File(info_file),
+            Category::Servidores,
+            &CsvOptions::default(),
+            &mut rejects,
+        )
+        .unwrap();
+        assert_eq!(info_values.len(), 2);
+
+        // The row with a role is the main one, and the copy of a link is kept once
+        let professor = &info_values["100"];
+        assert_eq!(professor.descricao_cargo, "PROFESSOR");
+        assert_eq!(
+            professor.vinculos,
+            vec![
+                Vinculo::new("PROFESSOR", "-", "INSTITUTO DE INFORMATICA", "UFRGS"),
+                Vinculo::new("", "DIRETOR", "REITORIA", "UFRGS"),
+            ]
+        );
+        assert_eq!(
+            info_values["200"].vinculos,
+            vec![Vinculo::new("ANALISTA", "", "DTI", "UFSC")]
+        );
+        drop(rejects);
+        assert!(fs::read_to_string(&rejects_file).unwrap().contains("ABC"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,154 @@
+use std::{error::Error, fmt};
+
+// Reference month of a payroll, saved in the records as YYYYMM
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Period {
+    pub year: u16,
+    pub month: u8,
+}
+
+impl Period {
+    pub fn new(year: u16, month: u8) -> Result<Period, Box<dyn Error>> {
+        if !(1..=12).contains(&month) {
+            return Err(From::from(format!("Invalid month {} in a period", month)));
+        }
+
+        Ok(Period { year, month })
+    }
+
+    // Accepts YYYYMM, YYYY-MM and MM/YYYY
+    pub fn parse(text: &str) -> Result<Period, Box<dyn Error>> {
+        let text = text.trim();
+        let (year, month) = if text.len() == 6 && text.bytes().all(|c| c.is_ascii_digit()) {
+            (&text[..4], &text[4..])
+        } else if let Some(position) = text.find('-') {
+            (&text[..position], &text[position + 1..])
+        } else if let Some(position) = text.find('/') {
+            (&text[position + 1..], &text[..position])
+        } else {
+            return Err(From::from(format!(
+                "Invalid period \"{}\", expected YYYYMM",
+                text
+            )));
+        };
+
+        match (year.parse::<u16>(), month.parse::<u8>()) {
+            (Ok(year), Ok(month)) => Period::new(year, month),
+            _ => Err(From::from(format!(
+                "Invalid period \"{}\", expected YYYYMM",
+                text
+            ))),
+        }
+    }
+
+    // From the ANO and MES columns of the Remuneracao file
+    pub fn from_columns(year: &str, month: &str) -> Option<Period> {
+        Period::new(year.trim().parse().ok()?, month.trim().parse().ok()?).ok()
+    }
+
+    // The Portal names the files after their period, e.g. 201807_Remuneracao.csv
+    pub fn detect(file: &str) -> Option<Period> {
+        let bytes = file.as_bytes();
+        (0..bytes.len().saturating_sub(5))
+            .filter(|&i| bytes[i..i + 6].iter().all(|c| c.is_ascii_digit()))
+            .filter(|&i| i == 0 || !bytes[i - 1].is_ascii_digit())
+            .filter(|&i| i + 6 == bytes.len() || !bytes[i + 6].is_ascii_digit())
+            .find_map(|i| Period::parse(&file[i..i + 6]).ok())
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}{:02}", self.year, self.month)
+    }
+}
+
+// Periods searched with --period, a single one (201807) or an inclusive range (201801:201812)
+pub struct PeriodRange {
+    start: Period,
+    end: Period,
+}
+
+impl PeriodRange {
+    pub fn parse(text: &str) -> Result<PeriodRange, Box<dyn Error>> {
+        let (start, end) = match text.find(':') {
+            Some(position) => (
+                Period::parse(&text[..position])?,
+                Period::parse(&text[position + 1..])?,
+            ),
+            None => {
+                let period = Period::parse(text)?;
+                (period, period)
+            }
+        };
+
+        if start > end {
+            return Err(From::from(format!(
+                "The period range {} ends before it starts",
+                text
+            )));
+        }
+
+        Ok(PeriodRange { start, end })
+    }
+
+    pub fn contains(&self, period: Period) -> bool {
+        self.start <= period && period <= self.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_the_three_formats() {
+        let july = Period { year: 2018, month: 7 };
+        assert_eq!(Period::parse("201807").unwrap(), july);
+        assert_eq!(Period::parse(" 2018-07 ").unwrap(), july);
+        assert_eq!(Period::parse("07/2018").unwrap(), july);
+        assert_eq!(Period::parse("7/2018").unwrap(), july);
+        assert_eq!(Period::parse("201807").unwrap().to_string(), "201807");
+
+        assert!(Period::parse("201813").is_err());
+        assert!(Period::parse("201800").is_err());
+        assert!(Period::parse("2018").is_err());
+        assert!(Period::parse("julho/2018").is_err());
+        assert!(Period::parse("").is_err());
+    }
+
+    #[test]
+    fn detect_finds_the_period_in_the_file_name() {
+        assert_eq!(
+            Period::detect("201807_Remuneracao.csv"),
+            Some(Period { year: 2018, month: 7 })
+        );
+        assert_eq!(
+            Period::detect("dados/Servidores_201912_Cadastro.csv"),
+            Some(Period { year: 2019, month: 12 })
+        );
+        assert_eq!(Period::detect("20180701_Remuneracao.csv"), None);
+        assert_eq!(Period::detect("Remuneracao.csv"), None);
+    }
+
+    #[test]
+    fn range_contains_its_periods() {
+        let range = PeriodRange::parse("201811:201902").unwrap();
+        for period in ["201811", "201812", "201901", "201902"].iter() {
+            assert!(range.contains(Period::parse(period).unwrap()));
+        }
+        for period in ["201810", "201903", "201911"].iter() {
+            assert!(!range.contains(Period::parse(period).unwrap()));
+        }
+
+        let range = PeriodRange::parse("201807").unwrap();
+        assert!(range.contains(Period { year: 2018, month: 7 }));
+        assert!(!range.contains(Period { year: 2018, month: 8 }));
+
+        // The whole range of the years, which isn't gone through
+        let range = PeriodRange::parse("000001:65535-12").unwrap();
+        assert!(range.contains(Period { year: 65535, month: 12 }));
+        assert!(PeriodRange::parse("201902:201811").is_err());
+        assert!(PeriodRange::parse("201811:").is_err());
+    }
+}
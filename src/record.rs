@@ -1,21 +1,25 @@
+use category::{self, Category};
+use date::{self, Date};
+use money;
+use parser::SEM_INFORMACAO;
+use period::Period;
+use vinculo::{self, Vinculo};
 use std::{fmt, str};
 use std::{
     io::{self, Write},
 };
 
+// Widths of the fields in the old databases, where every record had the same size
 pub const NAME_MAX_SIZE: usize = 40;
 pub const CPF_MAX_SIZE: usize = 15;
 pub const SALARY_MAX_SIZE: usize = 10;
 pub const DESCRIPTION_MAX_SIZE: usize = 50;
 pub const DATA_MAX_SIZE: usize = 12;
 pub const DEDICACAO_MAX_SIZE: usize = 20;
-pub const DATA_ENTRY_SIZE: usize = NAME_MAX_SIZE
-    + CPF_MAX_SIZE
-    + SALARY_MAX_SIZE * 10
-    + DESCRIPTION_MAX_SIZE * 2
-    + DATA_MAX_SIZE * 4
-    + DEDICACAO_MAX_SIZE;
-pub const RECORD_SIZES: [usize; 19] = [
+pub const CATEGORY_MAX_SIZE: usize = 15;
+pub const PERIOD_MAX_SIZE: usize = 6;
+pub const VINCULOS_MAX_SIZE: usize = 300;
+pub const RECORD_SIZES: [usize; 22] = [
     NAME_MAX_SIZE,
     SALARY_MAX_SIZE,
     CPF_MAX_SIZE,
@@ -35,7 +39,90 @@ pub const RECORD_SIZES: [usize; 19] = [
     DEDICACAO_MAX_SIZE,
     DATA_MAX_SIZE,
     DATA_MAX_SIZE,
+    CATEGORY_MAX_SIZE,
+    PERIOD_MAX_SIZE,
+    VINCULOS_MAX_SIZE,
 ];
+pub const FIELD_NAMES: [&str; 22] = [
+    "NOME",
+    "ID",
+    "CPF",
+    "DESCRICAO_CARGO",
+    "ORGAO_EXERCICIO",
+    "REMUNERACAO_BASICA_BRUTA",
+    "GRATIFICACAO_NATALINA",
+    "FERIAS",
+    "OUTRAS_REMUNERACOES_EVENTUAIS",
+    "IRRF",
+    "PSS_RGPS",
+    "DEMAIS_DEDUCOES",
+    "REMUNERACAO_APOS_DEDUCOES",
+    "TOTAL_VERBAS_INDENIZATORIAS",
+    "DATA_INICIO_AFASTAMENTO",
+    "DATA_TERMINO_AFASTAMENTO",
+    "JORNADA_TRABALHO",
+    "DATA_INGRESSO_CARGO",
+    "DATA_INGRESSO_ORGAO",
+    "CATEGORIA",
+    "PERIODO",
+    "VINCULOS",
+];
+
+// Positions in FIELD_NAMES of the fields which the code handles on their own
+pub const NOME: usize = 0;
+pub const ID: usize = 1;
+pub const CPF: usize = 2;
+pub const DESCRICAO_CARGO: usize = 3;
+pub const ORGAO_EXERCICIO: usize = 4;
+pub const REMUNERACAO_BASICA_BRUTA: usize = 5;
+pub const REMUNERACAO_APOS_DEDUCOES: usize = 12;
+pub const TOTAL_VERBAS_INDENIZATORIAS: usize = 13;
+pub const DATA_INICIO_AFASTAMENTO: usize = 14;
+pub const DATA_TERMINO_AFASTAMENTO: usize = 15;
+pub const DATA_INGRESSO_CARGO: usize = 17;
+pub const DATA_INGRESSO_ORGAO: usize = 18;
+pub const CATEGORIA: usize = 19;
+pub const PERIODO: usize = 20;
+pub const VINCULOS: usize = 21;
+
+// Index of a field, from its name in FIELD_NAMES
+pub fn field_index(name: &str) -> usize {
+    match FIELD_NAMES.iter().position(|field| *field == name) {
+        Some(index) => index,
+        None => panic!("The record doesn't have the field {}", name),
+    }
+}
+
+// Reads an answer typed after a prompt, which may be empty
+fn read_answer() -> String {
+    io::stdout().flush().unwrap();
+    if cfg!(windows) {
+        read!("{}\r\n")
+    } else {
+        read!("{}\n")
+    }
+}
+
+// Indexes of the money fields, kept in cents
+pub fn is_money_field(index: usize) -> bool {
+    (REMUNERACAO_BASICA_BRUTA..=TOTAL_VERBAS_INDENIZATORIAS).contains(&index)
+}
+
+// The missing dates are shown like in the Portal files
+fn date_text(date: Option<Date>) -> String {
+    match date {
+        Some(date) => date.to_string(),
+        None => SEM_INFORMACAO.to_string(),
+    }
+}
+
+// Indexes of the date fields, kept as dates instead of text
+pub fn is_date_field(index: usize) -> bool {
+    index == DATA_INICIO_AFASTAMENTO
+        || index == DATA_TERMINO_AFASTAMENTO
+        || index == DATA_INGRESSO_CARGO
+        || index == DATA_INGRESSO_ORGAO
+}
 
 #[derive(PartialOrd, PartialEq, Default)]
 pub struct Record {
@@ -44,20 +131,23 @@ pub struct Record {
     pub cpf: Vec<u8>,
     pub descricao_cargo: Vec<u8>,
     pub orgao_exercicio: Vec<u8>,
-    pub remuneracao_basica_bruta_rs: Vec<u8>,
-    pub gratificacao_natalina_rs: Vec<u8>,
-    pub ferias_rs: Vec<u8>,
-    pub outras_remuneracoes_eventuais_rs: Vec<u8>,
-    pub irrf_rs: Vec<u8>,
-    pub pss_rgps_rs: Vec<u8>,
-    pub demais_deducoes_rs: Vec<u8>,
-    pub remuneracao_apos_deducoes_obrigatorias_rs: Vec<u8>,
-    pub total_verbas_indenizatorias_rs: Vec<u8>,
-    pub data_inicio_afastamento: Vec<u8>,
-    pub data_termino_afastamento: Vec<u8>,
+    pub remuneracao_basica_bruta_rs: i64,
+    pub gratificacao_natalina_rs: i64,
+    pub ferias_rs: i64,
+    pub outras_remuneracoes_eventuais_rs: i64,
+    pub irrf_rs: i64,
+    pub pss_rgps_rs: i64,
+    pub demais_deducoes_rs: i64,
+    pub remuneracao_apos_deducoes_obrigatorias_rs: i64,
+    pub total_verbas_indenizatorias_rs: i64,
+    pub data_inicio_afastamento: Option<Date>,
+    pub data_termino_afastamento: Option<Date>,
     pub jornada_trabalho: Vec<u8>,
-    pub data_ingresso_cargo: Vec<u8>,
-    pub data_ingresso_orgao: Vec<u8>,
+    pub data_ingresso_cargo: Option<Date>,
+    pub data_ingresso_orgao: Option<Date>,
+    pub categoria: Vec<u8>,
+    pub periodo: Vec<u8>,
+    pub vinculos: Vec<u8>,
 }
 
 impl Record {
@@ -114,7 +204,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let remuneracao_bruta = str_remuneracao_bruta.as_bytes().to_vec();
+        let remuneracao_bruta = money::parse(&str_remuneracao_bruta).unwrap_or(0);
 
         print!("Digite o valor da sua gratificação natalina: R$ ");
         io::stdout().flush().unwrap();
@@ -123,7 +213,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let gratificacao_natalina = str_gratificacao_natalina.as_bytes().to_vec();
+        let gratificacao_natalina = money::parse(&str_gratificacao_natalina).unwrap_or(0);
 
         print!("Digite o valor das suas férias: R$ ");
         io::stdout().flush().unwrap();
@@ -132,7 +222,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let ferias = str_ferias.as_bytes().to_vec();
+        let ferias = money::parse(&str_ferias).unwrap_or(0);
 
         print!("Digite o valor de outras remunerações eventuais: R$ ");
         io::stdout().flush().unwrap();
@@ -141,7 +231,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let remuneracoes_eventuais = str_remuneracoes_eventuais.as_bytes().to_vec();
+        let remuneracoes_eventuais = money::parse(&str_remuneracoes_eventuais).unwrap_or(0);
 
         print!("Digite o valor que você paga de IRRF: R$ ");
         io::stdout().flush().unwrap();
@@ -150,7 +240,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let irrf = str_irrf.as_bytes().to_vec();
+        let irrf = money::parse(&str_irrf).unwrap_or(0);
 
         print!("Digite o valor que você paga de PSS: R$ ");
         io::stdout().flush().unwrap();
@@ -159,7 +249,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let pss = str_pss.as_bytes().to_vec();
+        let pss = money::parse(&str_pss).unwrap_or(0);
 
         print!("Digite o valor das demais deduções: R$ ");
         io::stdout().flush().unwrap();
@@ -168,7 +258,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let demais_deducoes = str_demais_deducoes.as_bytes().to_vec();
+        let demais_deducoes = money::parse(&str_demais_deducoes).unwrap_or(0);
 
         print!("Digite a sua remuneração após as deduções: R$ ");
         io::stdout().flush().unwrap();
@@ -177,7 +267,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let remuneracao_liquida = str_remuneracao_liquida.as_bytes().to_vec();
+        let remuneracao_liquida = money::parse(&str_remuneracao_liquida).unwrap_or(0);
 
         print!("Digite o valor recebido por verbas indenizatórias: R$ ");
         io::stdout().flush().unwrap();
@@ -186,7 +276,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let verbas_indenizatorias = str_verbas_indenizatorias.as_bytes().to_vec();
+        let verbas_indenizatorias = money::parse(&str_verbas_indenizatorias).unwrap_or(0);
 
         print!("Digite a data de início do seu afastamento (caso esteja afastado): ");
         io::stdout().flush().unwrap();
@@ -195,7 +285,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let inicio_afastamento = str_inicio_afastamento.as_bytes().to_vec();
+        let inicio_afastamento = Date::from_field(&str_inicio_afastamento);
 
         print!("Digite a data de término do seu afastamento (caso esteja afastado): ");
         io::stdout().flush().unwrap();
@@ -204,7 +294,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let termino_afastamento = str_termino_afastamento.as_bytes().to_vec();
+        let termino_afastamento = Date::from_field(&str_termino_afastamento);
 
         print!("Digite a sua jornada de trabalho: ");
         io::stdout().flush().unwrap();
@@ -222,7 +312,7 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let ingresso_cargo = str_ingresso_cargo.as_bytes().to_vec();
+        let ingresso_cargo = Date::from_field(&str_ingresso_cargo);
 
         print!("Digite a sua data de ingresso no órgão: ");
         io::stdout().flush().unwrap();
@@ -231,12 +321,39 @@ impl Record {
         } else {
             read!("{}\n")
         };
-        let ingresso_orgao = str_ingresso_orgao.as_bytes().to_vec();
+        let ingresso_orgao = Date::from_field(&str_ingresso_orgao);
+
+        print!("Digite a sua categoria ({}): ", category::CATEGORY_NAMES.join(", "));
+        io::stdout().flush().unwrap();
+        let str_categoria: String = if cfg!(windows) {
+            read!("{}\r\n")
+        } else {
+            read!("{}\n")
+        };
+        let categoria = Category::from_name(&str_categoria)
+            .unwrap_or(Category::Servidores)
+            .name()
+            .as_bytes()
+            .to_vec();
+
+        print!("Digite o período de referência (AAAAMM): ");
+        io::stdout().flush().unwrap();
+        let str_periodo: String = if cfg!(windows) {
+            read!("{}\r\n")
+        } else {
+            read!("{}\n")
+        };
+        let periodo = match Period::parse(&str_periodo) {
+            Ok(period) => period.to_string().as_bytes().to_vec(),
+            Err(_) => Vec::new(),
+        };
+
+        let vinculos = vinculo::to_text(&[Vinculo::new(&str_cargo, "", "", &str_orgao)]);
 
         Record {
-            nome: nome,
-            id: id,
-            cpf: cpf,
+            nome,
+            id,
+            cpf,
             descricao_cargo: cargo,
             orgao_exercicio: orgao,
             remuneracao_basica_bruta_rs: remuneracao_bruta,
@@ -253,6 +370,179 @@ impl Record {
             jornada_trabalho: jornada,
             data_ingresso_cargo: ingresso_cargo,
             data_ingresso_orgao: ingresso_orgao,
+            categoria,
+            periodo,
+            vinculos: vinculos.into_bytes(),
+        }
+    }
+
+    // Asks for the new value of each field, showing the current one. An empty answer, or an
+    // invalid one, keeps the current value
+    pub fn edit_from_stdin(&mut self) {
+        for (i, field_name) in FIELD_NAMES.iter().enumerate() {
+            if i == VINCULOS {
+                continue; // The links are edited one at a time, below
+            }
+
+            print!("{} [{}]: ", field_name, self.get(i));
+            let answer = read_answer();
+            if answer.is_empty() {
+                continue;
+            }
+
+            let value = if is_money_field(i) {
+                money::parse(&answer).map(|_| answer.clone())
+            } else if is_date_field(i) {
+                if date::is_missing(&answer) || Date::parse(&answer).is_ok() {
+                    Some(answer.clone())
+                } else {
+                    None
+                }
+            } else if i == CATEGORIA {
+                Category::from_name(&answer).ok().map(|category| category.name().to_string())
+            } else if i == PERIODO {
+                Period::parse(&answer).ok().map(|period| period.to_string())
+            } else {
+                Some(answer.clone())
+            };
+
+            match value {
+                Some(value) => self.set(i, &value),
+                None => println!("Invalid value, the current one was kept"),
+            }
+        }
+
+        self.edit_vinculo_from_stdin();
+    }
+
+    // Asks which of the links is changed, and the new value of each of its fields. The other
+    // links are kept as they are
+    fn edit_vinculo_from_stdin(&mut self) {
+        let mut vinculos = self.get_vinculos();
+        if vinculos.is_empty() {
+            return;
+        }
+
+        println!("\nVINCULOS:");
+        for (i, vinculo) in vinculos.iter().enumerate() {
+            println!("{}. {}", i + 1, vinculo);
+        }
+        print!("Link to be changed (1-{}, empty for none): ", vinculos.len());
+        let answer = read_answer();
+        if answer.is_empty() {
+            return;
+        }
+        let vinculo = match answer.trim().parse::<usize>() {
+            Ok(number) if number >= 1 && number <= vinculos.len() => &mut vinculos[number - 1],
+            _ => {
+                println!("Invalid link, the current ones were kept");
+                return;
+            }
+        };
+
+        for (name, value) in [
+            ("CARGO", &mut vinculo.cargo),
+            ("FUNCAO", &mut vinculo.funcao),
+            ("UORG", &mut vinculo.uorg),
+            ("ORGAO", &mut vinculo.orgao),
+        ] {
+            print!("{} [{}]: ", name, value);
+            let answer = read_answer();
+            if !answer.is_empty() {
+                *value = answer;
+            }
+        }
+        self.set_vinculos(&vinculos);
+    }
+
+    // The money fields are written as 8 bytes (little-endian) with their cents, the dates as 4
+    // bytes with YYYYMMDD (0 when missing), and each of the other ones with its length (4 bytes,
+    // little-endian) before it
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut size: usize = 0;
+        for i in 0..FIELD_NAMES.len() {
+            if let Some(cents) = self.get_money(i) {
+                writer.write_all(&cents.to_le_bytes())?;
+                size += 8;
+                continue;
+            }
+            if is_date_field(i) {
+                writer.write_all(&Date::to_number(self.get_date(i)).to_le_bytes())?;
+                size += 4;
+                continue;
+            }
+
+            let field = self.get(i);
+            writer.write_all(&(field.len() as u32).to_le_bytes())?;
+            writer.write_all(field.as_bytes())?;
+            size += 4 + field.len();
+        }
+
+        Ok(size)
+    }
+
+    // Cents of one of the money fields, or None for the other fields
+    pub fn get_money(&self, index: usize) -> Option<i64> {
+        match index {
+            5 => Some(self.remuneracao_basica_bruta_rs),
+            6 => Some(self.gratificacao_natalina_rs),
+            7 => Some(self.ferias_rs),
+            8 => Some(self.outras_remuneracoes_eventuais_rs),
+            9 => Some(self.irrf_rs),
+            10 => Some(self.pss_rgps_rs),
+            11 => Some(self.demais_deducoes_rs),
+            12 => Some(self.remuneracao_apos_deducoes_obrigatorias_rs),
+            13 => Some(self.total_verbas_indenizatorias_rs),
+            _ => None,
+        }
+    }
+
+    pub fn set_money(&mut self, index: usize, cents: i64) {
+        match index {
+            5 => self.remuneracao_basica_bruta_rs = cents,
+            6 => self.gratificacao_natalina_rs = cents,
+            7 => self.ferias_rs = cents,
+            8 => self.outras_remuneracoes_eventuais_rs = cents,
+            9 => self.irrf_rs = cents,
+            10 => self.pss_rgps_rs = cents,
+            11 => self.demais_deducoes_rs = cents,
+            12 => self.remuneracao_apos_deducoes_obrigatorias_rs = cents,
+            13 => self.total_verbas_indenizatorias_rs = cents,
+            _ => println!("Error!!"),
+        }
+    }
+
+    // One of the date fields, or None for the other fields too
+    pub fn get_date(&self, index: usize) -> Option<Date> {
+        match index {
+            14 => self.data_inicio_afastamento,
+            15 => self.data_termino_afastamento,
+            17 => self.data_ingresso_cargo,
+            18 => self.data_ingresso_orgao,
+            _ => None,
+        }
+    }
+
+    pub fn set_date(&mut self, index: usize, date: Option<Date>) {
+        match index {
+            14 => self.data_inicio_afastamento = date,
+            15 => self.data_termino_afastamento = date,
+            17 => self.data_ingresso_cargo = date,
+            18 => self.data_ingresso_orgao = date,
+            _ => println!("Error!!"),
+        }
+    }
+
+    // When the worker was admitted, in the role or, without its date, in the agency
+    pub fn admission_date(&self) -> Option<Date> {
+        self.data_ingresso_cargo.or(self.data_ingresso_orgao)
+    }
+
+    // Whether the worker was on leave on the day, with a leave without an end still going on
+    pub fn is_on_leave(&self, day: Date) -> bool {
+        match self.data_inicio_afastamento {
+            Some(start) => start <= day && self.data_termino_afastamento.is_none_or(|end| day <= end),
+            None => false,
         }
     }
 
@@ -276,60 +566,108 @@ impl Record {
         str::from_utf8(&self.orgao_exercicio).unwrap()
     }
 
-    fn get_remuneracao_bruta(&self) -> &str {
-        str::from_utf8(&self.remuneracao_basica_bruta_rs).unwrap()
+    fn get_remuneracao_bruta(&self) -> String {
+        money::format(self.remuneracao_basica_bruta_rs)
     }
 
-    fn get_gratificacao_natalina(&self) -> &str {
-        str::from_utf8(&self.gratificacao_natalina_rs).unwrap()
+    fn get_gratificacao_natalina(&self) -> String {
+        money::format(self.gratificacao_natalina_rs)
     }
 
-    fn get_ferias(&self) -> &str {
-        str::from_utf8(&self.ferias_rs).unwrap()
+    fn get_ferias(&self) -> String {
+        money::format(self.ferias_rs)
     }
 
-    fn get_outras_remuneracoes(&self) -> &str {
-        str::from_utf8(&self.outras_remuneracoes_eventuais_rs).unwrap()
+    fn get_outras_remuneracoes(&self) -> String {
+        money::format(self.outras_remuneracoes_eventuais_rs)
     }
 
-    fn get_irrf(&self) -> &str {
-        str::from_utf8(&self.irrf_rs).unwrap()
+    fn get_irrf(&self) -> String {
+        money::format(self.irrf_rs)
     }
 
-    fn get_pss(&self) -> &str {
-        str::from_utf8(&self.pss_rgps_rs).unwrap()
+    fn get_pss(&self) -> String {
+        money::format(self.pss_rgps_rs)
     }
 
-    fn get_demais_reducoes(&self) -> &str {
-        str::from_utf8(&self.demais_deducoes_rs).unwrap()
+    fn get_demais_reducoes(&self) -> String {
+        money::format(self.demais_deducoes_rs)
     }
 
-    fn get_remuneracao_apos_deducoes(&self) -> &str {
-        str::from_utf8(&self.remuneracao_apos_deducoes_obrigatorias_rs).unwrap()
+    fn get_remuneracao_apos_deducoes(&self) -> String {
+        money::format(self.remuneracao_apos_deducoes_obrigatorias_rs)
     }
 
-    fn get_verbas_indenizatorias(&self) -> &str {
-        str::from_utf8(&self.total_verbas_indenizatorias_rs).unwrap()
+    fn get_verbas_indenizatorias(&self) -> String {
+        money::format(self.total_verbas_indenizatorias_rs)
     }
 
-    fn get_data_inicio_afastamento(&self) -> &str {
-        str::from_utf8(&self.data_inicio_afastamento).unwrap()
+    fn get_data_inicio_afastamento(&self) -> String {
+        date_text(self.data_inicio_afastamento)
     }
 
-    fn get_data_termino_afastamento(&self) -> &str {
-        str::from_utf8(&self.data_termino_afastamento).unwrap()
+    fn get_data_termino_afastamento(&self) -> String {
+        date_text(self.data_termino_afastamento)
     }
 
     fn get_jornada_trabalho(&self) -> &str {
         str::from_utf8(&self.jornada_trabalho).unwrap()
     }
 
-    fn get_data_ingresso_cargo(&self) -> &str {
-        str::from_utf8(&self.data_ingresso_cargo).unwrap()
+    fn get_data_ingresso_cargo(&self) -> String {
+        date_text(self.data_ingresso_cargo)
+    }
+
+    fn get_data_ingresso_orgao(&self) -> String {
+        date_text(self.data_ingresso_orgao)
+    }
+
+    fn get_categoria(&self) -> &str {
+        str::from_utf8(&self.categoria).unwrap()
+    }
+
+    fn get_periodo(&self) -> &str {
+        str::from_utf8(&self.periodo).unwrap()
     }
 
-    fn get_data_ingresso_orgao(&self) -> &str {
-        str::from_utf8(&self.data_ingresso_orgao).unwrap()
+    fn get_raw_vinculos(&self) -> &str {
+        str::from_utf8(&self.vinculos).unwrap()
+    }
+
+    pub fn get_vinculos(&self) -> Vec<Vinculo> {
+        vinculo::from_text(self.get_raw_vinculos().trim_matches(char::from(0)))
+    }
+
+    pub fn set_vinculos(&mut self, vinculos: &[Vinculo]) {
+        self.vinculos = vinculo::to_text(vinculos).into_bytes();
+    }
+
+    // Words indexed by the tries for each field. The role and agency tries index the roles,
+    // functions, UORGs and agencies of every link, besides the main ones
+    pub fn indexed_words(&self, index: usize) -> Vec<String> {
+        let mut texts: Vec<String> = vec![self.get(index)];
+        for vinculo in self.get_vinculos() {
+            match index {
+                DESCRICAO_CARGO => {
+                    texts.push(vinculo.cargo);
+                    texts.push(vinculo.funcao);
+                }
+                ORGAO_EXERCICIO => {
+                    texts.push(vinculo.uorg);
+                    texts.push(vinculo.orgao);
+                }
+                _ => break,
+            }
+        }
+
+        let mut words: Vec<String> = texts
+            .iter()
+            .flat_map(|text| text.trim_matches(char::from(0)).split_whitespace())
+            .map(str::to_string)
+            .collect();
+        words.sort();
+        words.dedup();
+        words
     }
 
     pub fn get(&self, index: usize) -> String {
@@ -348,81 +686,89 @@ impl Record {
             11 => self.get_demais_reducoes().to_string(),
             12 => self.get_remuneracao_apos_deducoes().to_string(),
             13 => self.get_verbas_indenizatorias().to_string(),
-            14 => self.get_data_inicio_afastamento().to_string(),
-            15 => self.get_data_termino_afastamento().to_string(),
+            14 => self.get_data_inicio_afastamento(),
+            15 => self.get_data_termino_afastamento(),
             16 => self.get_jornada_trabalho().to_string(),
-            17 => self.get_data_ingresso_cargo().to_string(),
-            18 => self.get_data_ingresso_orgao().to_string(),
+            17 => self.get_data_ingresso_cargo(),
+            18 => self.get_data_ingresso_orgao(),
+            19 => self.get_categoria().to_string(),
+            20 => self.get_periodo().to_string(),
+            21 => self.get_raw_vinculos().to_string(),
             _ => "Error!!".to_string(),
         }
     }
 
+    pub fn set(&mut self, index: usize, text: &str) {
+        let value = text.as_bytes().to_vec();
+        match index {
+            0 => self.nome = value,
+            1 => self.id = value,
+            2 => self.cpf = value,
+            3 => self.descricao_cargo = value,
+            4 => self.orgao_exercicio = value,
+            5 => self.remuneracao_basica_bruta_rs = money::parse(text).unwrap_or(0),
+            6 => self.gratificacao_natalina_rs = money::parse(text).unwrap_or(0),
+            7 => self.ferias_rs = money::parse(text).unwrap_or(0),
+            8 => self.outras_remuneracoes_eventuais_rs = money::parse(text).unwrap_or(0),
+            9 => self.irrf_rs = money::parse(text).unwrap_or(0),
+            10 => self.pss_rgps_rs = money::parse(text).unwrap_or(0),
+            11 => self.demais_deducoes_rs = money::parse(text).unwrap_or(0),
+            12 => self.remuneracao_apos_deducoes_obrigatorias_rs = money::parse(text).unwrap_or(0),
+            13 => self.total_verbas_indenizatorias_rs = money::parse(text).unwrap_or(0),
+            14 => self.data_inicio_afastamento = Date::from_field(text),
+            15 => self.data_termino_afastamento = Date::from_field(text),
+            16 => self.jornada_trabalho = value,
+            17 => self.data_ingresso_cargo = Date::from_field(text),
+            18 => self.data_ingresso_orgao = Date::from_field(text),
+            19 => self.categoria = value,
+            20 => self.periodo = value,
+            21 => self.vinculos = value,
+            _ => println!("Error!!"),
+        }
+    }
+
+    // The roles and agencies of every link, one per line, falling back to the main ones
+    fn vinculos_cells(&self) -> (String, String) {
+        let vinculos = self.get_vinculos();
+        if vinculos.len() <= 1 {
+            return (
+                self.get_descricao_cargo().to_string(),
+                self.get_orgao_exercicio().to_string(),
+            );
+        }
+
+        let roles: Vec<String> = vinculos
+            .iter()
+            .map(|vinculo| match (vinculo.cargo.is_empty(), vinculo.funcao.is_empty()) {
+                (false, false) => format!("{} ({})", vinculo.cargo, vinculo.funcao),
+                (true, false) => vinculo.funcao.clone(),
+                _ => vinculo.cargo.clone(),
+            }).collect();
+        let agencies: Vec<String> = vinculos.iter().map(|vinculo| vinculo.orgao.clone()).collect();
+
+        (roles.join("\n"), agencies.join("\n"))
+    }
+
     pub fn generate_csv_string(&mut self) -> String {
         let mut return_string: String = String::new();
-
-        return_string += &(self.get_name().to_owned() + &";".to_string());
-        return_string += &(self.get_descricao_cargo().to_owned() + &";".to_string());
-        return_string += &(self.get_orgao_exercicio().to_owned() + &";".to_string());
-        return_string += &(self.get_remuneracao_bruta().to_owned() + &";".to_string());
-        return_string += &(self.get_gratificacao_natalina().to_owned() + &";".to_string());
-        return_string += &(self.get_irrf().to_owned() + &";".to_string());
-        return_string += &(self.get_pss().to_owned() + &";".to_string());
-        return_string += &(self.get_demais_reducoes().to_owned() + &";".to_string());
-        return_string += &(self.get_remuneracao_apos_deducoes().to_owned() + &";".to_string());
-        return_string += self.get_verbas_indenizatorias(); // No ';' in the final one
+        let (roles, agencies) = self.vinculos_cells();
+
+        return_string += &(self.get_name().to_owned() + ";");
+        return_string += &(self.get_categoria().to_owned() + ";");
+        return_string += &(self.get_periodo().to_owned() + ";");
+        // Quoted, as they may have more than one line
+        return_string += &format!("\"{}\";", roles.replace('"', "\"\""));
+        return_string += &format!("\"{}\";", agencies.replace('"', "\"\""));
+        return_string += &(self.get_remuneracao_bruta().to_owned() + ";");
+        return_string += &(self.get_gratificacao_natalina().to_owned() + ";");
+        return_string += &(self.get_irrf().to_owned() + ";");
+        return_string += &(self.get_pss().to_owned() + ";");
+        return_string += &(self.get_demais_reducoes().to_owned() + ";");
+        return_string += &(self.get_remuneracao_apos_deducoes().to_owned() + ";");
+        return_string += &self.get_verbas_indenizatorias(); // No ';' in the final one
         return_string += "\n";
 
-        return return_string;
-    }
-
-    pub fn as_u8_array(&mut self) -> Vec<u8> {
-        let mut vec: Vec<u8> = Vec::new();
-        vec.append(&mut self.nome);
-        vec.append(&mut self.id);
-        vec.append(&mut self.cpf);
-        vec.append(&mut self.descricao_cargo);
-        vec.append(&mut self.orgao_exercicio);
-        vec.append(&mut self.remuneracao_basica_bruta_rs);
-        vec.append(&mut self.gratificacao_natalina_rs);
-        vec.append(&mut self.ferias_rs);
-        vec.append(&mut self.outras_remuneracoes_eventuais_rs);
-        vec.append(&mut self.irrf_rs);
-        vec.append(&mut self.pss_rgps_rs);
-        vec.append(&mut self.demais_deducoes_rs);
-        vec.append(&mut self.remuneracao_apos_deducoes_obrigatorias_rs);
-        vec.append(&mut self.total_verbas_indenizatorias_rs);
-        vec.append(&mut self.data_inicio_afastamento);
-        vec.append(&mut self.data_termino_afastamento);
-        vec.append(&mut self.jornada_trabalho);
-        vec.append(&mut self.data_ingresso_cargo);
-        vec.append(&mut self.data_ingresso_orgao);
-
-        return vec;
-    }
-
-    pub fn resize(&mut self) {
-        self.nome.resize(NAME_MAX_SIZE, 0);
-        self.id.resize(SALARY_MAX_SIZE, 0);
-        self.cpf.resize(CPF_MAX_SIZE, 0);
-        self.descricao_cargo.resize(DESCRIPTION_MAX_SIZE, 0);
-        self.orgao_exercicio.resize(DESCRIPTION_MAX_SIZE, 0);
-        self.remuneracao_basica_bruta_rs.resize(SALARY_MAX_SIZE, 0);
-        self.gratificacao_natalina_rs.resize(SALARY_MAX_SIZE, 0);
-        self.ferias_rs.resize(SALARY_MAX_SIZE, 0);
-        self.outras_remuneracoes_eventuais_rs
-            .resize(SALARY_MAX_SIZE, 0);
-        self.irrf_rs.resize(SALARY_MAX_SIZE, 0);
-        self.pss_rgps_rs.resize(SALARY_MAX_SIZE, 0);
-        self.demais_deducoes_rs.resize(SALARY_MAX_SIZE, 0);
-        self.remuneracao_apos_deducoes_obrigatorias_rs
-            .resize(SALARY_MAX_SIZE, 0);
-        self.total_verbas_indenizatorias_rs
-            .resize(SALARY_MAX_SIZE, 0);
-        self.data_inicio_afastamento.resize(DATA_MAX_SIZE, 0);
-        self.data_termino_afastamento.resize(DATA_MAX_SIZE, 0);
-        self.jornada_trabalho.resize(DEDICACAO_MAX_SIZE, 0);
-        self.data_ingresso_cargo.resize(DATA_MAX_SIZE, 0);
-        self.data_ingresso_orgao.resize(DATA_MAX_SIZE, 0);
+        return_string
     }
 }
 
@@ -431,9 +777,12 @@ impl fmt::Display for Record {
         write!(
             f,
             "Id do Servidor: {}\n\
+             Categoria: {}\n\
+             Período de Referência: {}\n\
              Nome & CPF do Servidor Público: {} - {}\n\
              Cargo: {}\n\
              Órgao em Exercício: {}\n\
+             Vínculos: {}\n\
              Remuneração Bruta: R$ {}\n\
              Gratificação Natalina: R${}\n\
              Ferias: R$ {}\n\
@@ -448,10 +797,17 @@ impl fmt::Display for Record {
              Data de Ingresso no Cargo: {}\n\
              Data de Ingresso no Orgao: {}",
             self.get_id(),
+            self.get_categoria(),
+            self.get_periodo(),
             self.get_name(),
             self.get_cpf(),
             self.get_descricao_cargo(),
             self.get_orgao_exercicio(),
+            self.get_vinculos()
+                .iter()
+                .map(|vinculo| vinculo.to_string())
+                .collect::<Vec<String>>()
+                .join("; "),
             self.get_remuneracao_bruta(),
             self.get_gratificacao_natalina(),
             self.get_ferias(),
@@ -461,15 +817,13 @@ impl fmt::Display for Record {
             self.get_demais_reducoes(),
             self.get_remuneracao_apos_deducoes(),
             self.get_verbas_indenizatorias(),
-            if self.data_inicio_afastamento.len() > 1 {
-                self.get_data_inicio_afastamento()
-            } else {
-                "Não está afastado"
+            match self.data_inicio_afastamento {
+                Some(start) => start.to_string(),
+                None => "Não está afastado".to_string(),
             },
-            if self.data_termino_afastamento.len() > 1 {
-                "até ".to_owned() + self.get_data_termino_afastamento()
-            } else {
-                " ".to_string()
+            match self.data_termino_afastamento {
+                Some(end) => format!("até {}", end),
+                None => " ".to_string(),
             },
             self.get_jornada_trabalho(),
             self.get_data_ingresso_cargo(),
@@ -477,3 +831,32 @@ impl fmt::Display for Record {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_positions_match_their_names() {
+        let positions = [
+            (NOME, "NOME"),
+            (ID, "ID"),
+            (CPF, "CPF"),
+            (DESCRICAO_CARGO, "DESCRICAO_CARGO"),
+            (ORGAO_EXERCICIO, "ORGAO_EXERCICIO"),
+            (REMUNERACAO_BASICA_BRUTA, "REMUNERACAO_BASICA_BRUTA"),
+            (REMUNERACAO_APOS_DEDUCOES, "REMUNERACAO_APOS_DEDUCOES"),
+            (TOTAL_VERBAS_INDENIZATORIAS, "TOTAL_VERBAS_INDENIZATORIAS"),
+            (DATA_INICIO_AFASTAMENTO, "DATA_INICIO_AFASTAMENTO"),
+            (DATA_TERMINO_AFASTAMENTO, "DATA_TERMINO_AFASTAMENTO"),
+            (DATA_INGRESSO_CARGO, "DATA_INGRESSO_CARGO"),
+            (DATA_INGRESSO_ORGAO, "DATA_INGRESSO_ORGAO"),
+            (CATEGORIA, "CATEGORIA"),
+            (PERIODO, "PERIODO"),
+            (VINCULOS, "VINCULOS"),
+        ];
+        for (position, name) in positions.iter() {
+            assert_eq!(field_index(name), *position);
+        }
+    }
+}
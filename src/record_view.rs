@@ -0,0 +1,230 @@
+use date::Date;
+use header::{FileKind, Header, HEADER_SIZE};
+use memmap2::Mmap;
+use parser::{self, DELETED_ENTRY};
+use record::{self, Record, FIELD_NAMES};
+use std::{error::Error, fs::File, io, str};
+use vinculo::{VINCULO_FIELD_SEPARATOR, VINCULO_SEPARATOR};
+
+// A record read straight from the memory-mapped database, without copying its fields. The
+// positions of the fields are found when it is created, following the lengths before them
+pub struct RecordView<'a> {
+    bytes: &'a [u8],
+    fields: [(usize, usize); 22],
+}
+
+fn cut_record(field_name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("The {} of a record goes past the end of the database", field_name),
+    )
+}
+
+impl<'a> RecordView<'a> {
+    // Reads the record at the beginning of `bytes`, which may have other records after it
+    pub fn parse(bytes: &'a [u8]) -> io::Result<RecordView<'a>> {
+        let mut fields = [(0, 0); 22];
+        let mut position: usize = 0;
+
+        for (i, field_name) in FIELD_NAMES.iter().enumerate() {
+            let size = if record::is_money_field(i) {
+                8
+            } else if record::is_date_field(i) {
+                4
+            } else {
+                let length = bytes
+                    .get(position..position + 4)
+                    .ok_or_else(|| cut_record(field_name))?;
+                position += 4;
+                u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize
+            };
+
+            let field = bytes
+                .get(position..position + size)
+                .ok_or_else(|| cut_record(field_name))?;
+            if !record::is_money_field(i) && !record::is_date_field(i) && str::from_utf8(field).is_err() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("The {} of a record isn't valid UTF-8", field_name),
+                ));
+            }
+
+            fields[i] = (position, position + size);
+            position += size;
+        }
+
+        Ok(RecordView {
+            bytes: &bytes[..position],
+            fields,
+        })
+    }
+
+    fn field(&self, index: usize) -> &'a [u8] {
+        let (start, end) = self.fields[index];
+        &self.bytes[start..end]
+    }
+
+    // One of the text fields, already checked to be valid UTF-8
+    pub fn text(&self, index: usize) -> &'a str {
+        str::from_utf8(self.field(index)).unwrap_or("")
+    }
+
+    pub fn money(&self, index: usize) -> i64 {
+        let mut cents = [0; 8];
+        cents.copy_from_slice(self.field(index));
+        i64::from_le_bytes(cents)
+    }
+
+    pub fn date(&self, index: usize) -> Option<Date> {
+        let mut number = [0; 4];
+        number.copy_from_slice(self.field(index));
+        Date::from_number(u32::from_le_bytes(number))
+    }
+
+    // Same as Record::indexed_words, for the text fields
+    pub fn indexed_words(&self, index: usize) -> Vec<&'a str> {
+        let mut texts: Vec<&'a str> = vec![self.text(index)];
+        if index == record::DESCRICAO_CARGO || index == record::ORGAO_EXERCICIO {
+            for link in self.text(record::VINCULOS).split(VINCULO_SEPARATOR) {
+                let fields: Vec<&str> = link.split(VINCULO_FIELD_SEPARATOR).collect();
+                let start = if index == record::DESCRICAO_CARGO { 0 } else { 2 };
+                texts.extend(fields.iter().skip(start).take(2));
+            }
+        }
+
+        let mut words: Vec<&'a str> = texts
+            .iter()
+            .flat_map(|text| text.trim_matches(char::from(0)).split_whitespace())
+            .collect();
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    pub fn to_record(&self) -> Record {
+        let mut record = Record::default();
+        for i in 0..FIELD_NAMES.len() {
+            if record::is_money_field(i) {
+                record.set_money(i, self.money(i));
+            } else if record::is_date_field(i) {
+                record.set_date(i, self.date(i));
+            } else {
+                record.set(i, self.text(i));
+            }
+        }
+
+        record
+    }
+}
+
+// The database and its index mapped in the memory, so the records are read without a system
+// call for each of them
+pub struct MappedDatabase {
+    database_file: String,
+    database: Mmap,
+    index: Mmap,
+    pub header: Header,
+}
+
+fn map(file: &File) -> io::Result<Mmap> {
+    // The files are only changed by this program, which doesn't write to them while they are
+    // mapped, so their contents don't change under the map
+    unsafe { Mmap::map(file) }
+}
+
+impl MappedDatabase {
+    pub fn open(database_file: &str) -> Result<MappedDatabase, Box<dyn Error>> {
+        let mut file = File::open(database_file)?;
+        let header = Header::read(&mut file, database_file, FileKind::Database)?;
+
+        Ok(MappedDatabase {
+            database_file: database_file.to_string(),
+            database: map(&file)?,
+            index: map(&File::open(parser::index_file(database_file))?)?,
+            header,
+        })
+    }
+
+    // The records, after the header, which the checksum is calculated from
+    pub fn body(&self) -> &[u8] {
+        self.database.get(HEADER_SIZE as usize..).unwrap_or(&[])
+    }
+
+    pub fn index_len(&self) -> usize {
+        self.index.len()
+    }
+
+    // Where the record of an entry (starting at 1) is, with the flag of the deleted ones
+    fn offset(&self, entry: u32) -> Result<u64, Box<dyn Error>> {
+        let position = (entry as usize - 1) * 8;
+        match self.index.get(position..position + 8) {
+            Some(offset) => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(offset);
+                Ok(u64::from_le_bytes(bytes))
+            }
+            None => Err(From::from(format!(
+                "The index of the database {} is missing the entry {}",
+                self.database_file, entry
+            ))),
+        }
+    }
+
+    // The record of an entry (starting at 1), or None when there is no such entry or it was
+    // deleted
+    pub fn get(&self, entry: u32) -> Result<Option<RecordView<'_>>, Box<dyn Error>> {
+        if entry == 0 || u64::from(entry) > self.header.records_count {
+            return Ok(None);
+        }
+
+        let offset = self.offset(entry)?;
+        if offset & DELETED_ENTRY != 0 {
+            return Ok(None);
+        }
+
+        let offset = offset as usize;
+        match self.database.get(offset..) {
+            Some(bytes) if offset >= HEADER_SIZE as usize => Ok(Some(RecordView::parse(bytes)?)),
+            _ => Err(From::from(format!(
+                "The entry {} points outside of the database {}",
+                entry, self.database_file
+            ))),
+        }
+    }
+
+    // Goes through the record of every entry, in order, skipping the deleted ones, after
+    // checking the checksum and the quantity of records. The old versions of the updated records
+    // are still in the database, but aren't pointed by any entry
+    pub fn for_each<F: FnMut(u32, RecordView)>(&self, mut function: F) -> Result<(), Box<dyn Error>> {
+        if crc32fast::hash(self.body()) != self.header.checksum {
+            return Err(From::from(format!(
+                "The database {} is corrupted (its checksum is wrong)",
+                self.database_file
+            )));
+        }
+        if self.index.len() as u64 != self.header.records_count * 8 {
+            return Err(From::from(format!(
+                "The database {} is corrupted (its index has {} entries and should have {})",
+                self.database_file,
+                self.index.len() / 8,
+                self.header.records_count
+            )));
+        }
+
+        for entry in 1..=self.header.records_count as u32 {
+            let view = match self.get(entry) {
+                Ok(Some(view)) => view,
+                Ok(None) => continue,
+                Err(err) => {
+                    return Err(From::from(format!(
+                        "The database {} is corrupted at the entry {} ({})",
+                        self.database_file, entry, err
+                    )))
+                }
+            };
+            function(entry, view);
+        }
+
+        Ok(())
+    }
+}
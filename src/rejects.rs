@@ -0,0 +1,95 @@
+use csv::{StringRecord, Writer};
+use std::{collections::BTreeMap, error::Error, fs::File};
+
+pub const DEFAULT_REJECTS_FILE: &str = "rejects.csv";
+
+// Why a line of the CSV files was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reason {
+    InvalidRow,
+    WrongColumnCount,
+    InvalidId,
+    InvalidMoney,
+    InvalidDate,
+}
+
+impl Reason {
+    fn label(self) -> &'static str {
+        match self {
+            Reason::InvalidRow => "invalid row",
+            Reason::WrongColumnCount => "wrong column count",
+            Reason::InvalidId => "invalid ID",
+            Reason::InvalidMoney => "unparsable money",
+            Reason::InvalidDate => "unparsable date",
+        }
+    }
+}
+
+// The lines with problems found while loading the CSV files, which are written to a CSV file with
+// the reason of each of them, so the load goes on instead of stopping at the first bad line.
+// The file is only created when the first line is written to it
+pub struct Rejects {
+    rejects_file: String,
+    writer: Option<Writer<File>>,
+    counts: BTreeMap<Reason, u32>,
+}
+
+impl Rejects {
+    pub fn new(rejects_file: &str) -> Rejects {
+        Rejects {
+            rejects_file: rejects_file.to_string(),
+            writer: None,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        csv_file: &str,
+        line: u64,
+        reason: Reason,
+        detail: &str,
+        row: Option<&StringRecord>,
+    ) -> Result<(), Box<dyn Error>> {
+        *self.counts.entry(reason).or_insert(0) += 1;
+
+        if self.writer.is_none() {
+            let mut writer = Writer::from_path(&self.rejects_file)?;
+            writer.write_record(["ARQUIVO", "LINHA", "MOTIVO", "DETALHE", "LINHA_ORIGINAL"])?;
+            self.writer = Some(writer);
+        }
+
+        let line = line.to_string();
+        let original = match row {
+            Some(row) => row.iter().collect::<Vec<&str>>().join(";"),
+            None => String::new(),
+        };
+        if let Some(ref mut writer) = self.writer {
+            writer.write_record([csv_file, &line, reason.label(), detail, &original])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn print_summary(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(ref mut writer) = self.writer {
+            writer.flush()?;
+        }
+
+        let rejected: u32 = self.counts.values().sum();
+        if rejected == 0 {
+            println!("\nEvery line of the CSV files was loaded without problems");
+            return Ok(());
+        }
+
+        println!(
+            "\n{} lines were rejected, see {}",
+            rejected, self.rejects_file
+        );
+        for (reason, count) in self.counts.iter() {
+            println!("  {}: {}", reason.label(), count);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+// The entries replaced by --update since the tries and the indexes were generated, which still
+// have the words and the values of their old records. They are listed next to the database, e.g.
+// database.replaced, with 4 bytes (little-endian) for each, so the searches check them against
+// their current records. The list is removed when the tries and the indexes are generated again
+pub fn replaced_file(database_file: &str) -> String {
+    Path::new(database_file)
+        .with_extension("replaced")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Called before the entry is replaced, so an interruption only leaves an entry which is checked
+// without need
+pub fn add(database_file: &str, entry: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(replaced_file(database_file))?;
+    file.write_all(&entry.to_le_bytes())?;
+    file.sync_all()
+}
+
+pub fn entries(database_file: &str) -> io::Result<HashSet<u32>> {
+    let bytes = match fs::read(replaced_file(database_file)) {
+        Ok(bytes) => bytes,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(err) => return Err(err),
+    };
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|entry| u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]))
+        .collect())
+}
+
+pub fn remove(database_file: &str) -> io::Result<()> {
+    let replaced_file = replaced_file(database_file);
+    if Path::new(&replaced_file).exists() {
+        fs::remove_file(replaced_file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn lists_the_replaced_entries_until_removed() {
+        let dir = env::temp_dir().join(format!("fwb_replaced_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let database_file = dir.join("database.bin").to_string_lossy().into_owned();
+
+        assert!(entries(&database_file).unwrap().is_empty());
+        for entry in [10, 3, 10, 70000].iter() {
+            add(&database_file, *entry).unwrap();
+        }
+        let mut replaced: Vec<u32> = entries(&database_file).unwrap().into_iter().collect();
+        replaced.sort();
+        assert_eq!(replaced, vec![3, 10, 70000]);
+
+        remove(&database_file).unwrap();
+        assert!(entries(&database_file).unwrap().is_empty());
+        remove(&database_file).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
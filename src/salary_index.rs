@@ -0,0 +1,70 @@
+use data_dir;
+use header::FileKind;
+use parser;
+use record;
+use record_view::MappedDatabase;
+use replaced;
+use sorted_index;
+use std::error::Error;
+
+// The records sorted by one of their salaries, in cents, so the ones within a range of salaries
+// are found without reading the database
+pub struct SalaryField {
+    pub label: &'static str,
+    pub index_file: &'static str,
+    pub record_index: usize,
+}
+
+impl SalaryField {
+    pub fn index_path(&self) -> String {
+        data_dir::path(self.index_file)
+    }
+}
+
+pub const GROSS_SALARY_INDEX: SalaryField = SalaryField {
+    label: "gross salary",
+    index_file: "gross_salary_index.bin",
+    record_index: record::REMUNERACAO_BASICA_BRUTA,
+};
+
+pub const NET_SALARY_INDEX: SalaryField = SalaryField {
+    label: "net salary",
+    index_file: "net_salary_index.bin",
+    record_index: record::REMUNERACAO_APOS_DEDUCOES,
+};
+
+pub const SALARY_FIELDS: [SalaryField; 2] = [GROSS_SALARY_INDEX, NET_SALARY_INDEX];
+
+pub fn new_from_database(field: &SalaryField) -> Result<(), Box<dyn Error>> {
+    let database = MappedDatabase::open(&parser::database_file())?;
+    let mut entries: Vec<(i64, u32)> = Vec::new();
+    database.for_each(|entry, view| {
+        entries.push((view.money(field.record_index), entry));
+    })?;
+
+    sorted_index::save(&field.index_path(), FileKind::SalaryIndex, &database.header, entries)
+}
+
+// Entries of the records with the salary between `min` and `max` (both included, in cents). The
+// records inserted after the index was generated aren't in it, and the replaced ones have their old
+// salary there, so they are searched too, and every record found is checked in the database
+pub fn search(field: &SalaryField, min: Option<i64>, max: Option<i64>) -> Result<Vec<u32>, Box<dyn Error>> {
+    let min = min.unwrap_or(i64::MIN);
+    let max = max.unwrap_or(i64::MAX);
+    let (mut candidates, indexed_count) =
+        sorted_index::search(&field.index_path(), FileKind::SalaryIndex, field.label, min, max)?;
+    candidates.extend(sorted_index::inserted_entries(indexed_count)?);
+    candidates.extend(replaced::entries(&parser::database_file())?);
+    candidates.sort();
+    candidates.dedup();
+
+    let mut entries: Vec<u32> = Vec::new();
+    for (entry, record) in parser::records_from_entries(candidates)? {
+        let cents = record.get_money(field.record_index).unwrap_or(0);
+        if min <= cents && cents <= max {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,189 @@
+use atomic_file;
+use crc32fast::Hasher;
+use header::{FileKind, Header, HEADER_SIZE};
+use parser;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+// Index files with the records sorted by a number, so the ones with a number within a range are
+// found with a binary search in the disk, without reading the database. Each entry has the number
+// (8 bytes, little-endian) and the entry of the record (4 bytes), after the header
+const ENTRY_SIZE: u64 = 12;
+
+// Sorts the numbers, one for each entry of the database (except the deleted ones), and saves them
+// in the index file. Its header keeps the quantity of records of the database, so the ones
+// inserted later are known
+pub fn save(
+    index_file: &str,
+    kind: FileKind,
+    database_header: &Header,
+    mut entries: Vec<(i64, u32)>,
+) -> Result<(), Box<dyn Error>> {
+    entries.sort();
+
+    // The index comes from the same files as the database
+    let mut header = Header::new(kind);
+    header.sources = database_header.sources.clone();
+    header.sources_count = database_header.sources_count;
+    header.records_count = database_header.records_count;
+
+    let mut output_file = BufWriter::new(atomic_file::create(index_file)?);
+    let mut hasher = Hasher::new();
+    output_file.write_all(&header.to_bytes())?;
+    for (number, entry) in entries {
+        let mut bytes: Vec<u8> = Vec::with_capacity(ENTRY_SIZE as usize);
+        bytes.extend_from_slice(&number.to_le_bytes());
+        bytes.extend_from_slice(&entry.to_le_bytes());
+        hasher.update(&bytes);
+        output_file.write_all(&bytes)?;
+    }
+
+    // Now that the entries are known, the header gets their checksum
+    header.checksum = hasher.finalize();
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.write_all(&header.to_bytes())?;
+    atomic_file::commit(index_file, output_file)?;
+
+    Ok(())
+}
+
+// The entries pointed by an index file, after checking its checksum and that they are sorted
+pub fn check_file(index_file: &str, kind: FileKind) -> Result<(Header, Vec<u32>), Box<dyn Error>> {
+    let bytes = fs::read(index_file)?;
+    let header = Header::read(&mut &bytes[..], index_file, kind)?;
+    let body = bytes.get(HEADER_SIZE as usize..).unwrap_or(&[]);
+    if !(body.len() as u64).is_multiple_of(ENTRY_SIZE) {
+        return Err(From::from("it ends in the middle of an entry"));
+    }
+
+    let mut reader = body;
+    let mut entries: Vec<(i64, u32)> = Vec::new();
+    while !reader.is_empty() {
+        entries.push(read_entry(&mut reader)?);
+    }
+    if entries.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Err(From::from("its entries aren't sorted"));
+    }
+    if crc32fast::hash(body) != header.checksum {
+        return Err(From::from("its checksum is wrong"));
+    }
+
+    Ok((header, entries.into_iter().map(|(_, entry)| entry).collect()))
+}
+
+fn read_entry<R: Read>(reader: &mut R) -> io::Result<(i64, u32)> {
+    let mut number = [0; 8];
+    let mut entry = [0; 4];
+    reader.read_exact(&mut number)?;
+    reader.read_exact(&mut entry)?;
+
+    Ok((i64::from_le_bytes(number), u32::from_le_bytes(entry)))
+}
+
+// Entries with the number between `min` and `max` (both included), and the quantity of records
+// of the database when the index was generated. The ones inserted after it aren't in the index
+pub fn search(
+    index_file: &str,
+    kind: FileKind,
+    label: &str,
+    min: i64,
+    max: i64,
+) -> Result<(Vec<u32>, u64), Box<dyn Error>> {
+    if !Path::new(index_file).exists() {
+        return Err(From::from(format!(
+            "The {} index {} doesn't exist, generate it with -t",
+            label, index_file
+        )));
+    }
+
+    let mut index = File::open(index_file)?;
+    let header = Header::read(&mut index, index_file, kind)?;
+    let entries_count = index.metadata()?.len().saturating_sub(HEADER_SIZE) / ENTRY_SIZE;
+    let mut entries: Vec<u32> = Vec::new();
+
+    // Finds the first entry with a number of at least `min`
+    let (mut start, mut end) = (0, entries_count);
+    while start < end {
+        let middle = start + (end - start) / 2;
+        index.seek(SeekFrom::Start(HEADER_SIZE + middle * ENTRY_SIZE))?;
+        if read_entry(&mut index)?.0 < min {
+            start = middle + 1;
+        } else {
+            end = middle;
+        }
+    }
+
+    // And reads from it until the numbers get bigger than `max`
+    index.seek(SeekFrom::Start(HEADER_SIZE + start * ENTRY_SIZE))?;
+    let mut reader = BufReader::new(index);
+    for _ in start..entries_count {
+        let (number, entry) = read_entry(&mut reader)?;
+        if number > max {
+            break;
+        }
+        entries.push(entry);
+    }
+
+    Ok((entries, header.records_count))
+}
+
+// Entries inserted in the database after an index with `indexed_count` records was generated
+pub fn inserted_entries(indexed_count: u64) -> Result<Vec<u32>, Box<dyn Error>> {
+    let records_count = parser::records_count(&parser::database_file())?;
+    Ok((indexed_count + 1..=records_count)
+        .map(|entry| entry as u32)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn search_finds_the_entries_within_the_range() {
+        let dir = env::temp_dir().join(format!("fwb_sorted_index_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let index_file = dir.join("gross_salary_index.bin").to_string_lossy().into_owned();
+
+        let mut database_header = Header::new(FileKind::Database);
+        database_header.records_count = 8;
+        let entries = vec![
+            (500_000, 1),
+            (-1_000, 2),
+            (120_000, 3),
+            (120_000, 4),
+            (0, 5),
+            (999_999, 6),
+            (120_001, 7),
+            (119_999, 8),
+        ];
+        save(&index_file, FileKind::SalaryIndex, &database_header, entries).unwrap();
+
+        let find = |min: i64, max: i64| {
+            let (mut found, indexed_count) = search(&index_file, FileKind::SalaryIndex, "gross salary", min, max).unwrap();
+            assert_eq!(indexed_count, 8);
+            found.sort();
+            found
+        };
+        assert_eq!(find(120_000, 120_000), vec![3, 4]);
+        assert_eq!(find(119_999, 120_001), vec![3, 4, 7, 8]);
+        assert_eq!(find(i64::MIN, 0), vec![2, 5]);
+        assert_eq!(find(500_000, i64::MAX), vec![1, 6]);
+        assert_eq!(find(200_000, 400_000), Vec::<u32>::new());
+        assert_eq!(find(1_000_000, i64::MAX), Vec::<u32>::new());
+        assert_eq!(find(i64::MIN, i64::MAX).len(), 8);
+
+        // The file is sorted, and the other kind of index isn't accepted
+        let (header, checked) = check_file(&index_file, FileKind::SalaryIndex).unwrap();
+        assert_eq!(header.records_count, 8);
+        assert_eq!(checked, vec![2, 5, 8, 3, 4, 7, 1, 6]);
+        assert!(search(&index_file, FileKind::LookupIndex, "ID", 0, 0).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
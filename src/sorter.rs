@@ -0,0 +1,256 @@
+use category::Category;
+use columns::{Aliases, Column, ColumnMap, SALARY_COLUMNS};
+use csv::{ByteRecord, ReaderBuilder, Writer, WriterBuilder};
+use encoding;
+use encoding::Encoding;
+use parser;
+use rejects::{Reason, Rejects};
+use source::CsvSource;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    process, str,
+};
+
+// Quantity of lines kept in memory before being flushed to a sorted chunk in the disk
+pub const SORT_CHUNK_SIZE: usize = 250_000;
+
+// Sorting key used by the old Python script: (NOME, Id_SERVIDOR_PORTAL as a number)
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    name: Vec<u8>,
+    id: u64,
+}
+
+// Positions of the columns used as the sorting key
+#[derive(Clone, Copy)]
+struct KeyColumns {
+    name: usize,
+    id: usize,
+}
+
+impl SortKey {
+    fn from_record(record: &ByteRecord, columns: KeyColumns) -> Option<SortKey> {
+        let name = record.get(columns.name)?;
+        let id = str::from_utf8(record.get(columns.id)?)
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()?;
+
+        Some(SortKey {
+            name: name.to_vec(),
+            id,
+        })
+    }
+}
+
+// One line waiting to be merged, remembering which chunk it came from
+struct MergeEntry {
+    key: SortKey,
+    record: ByteRecord,
+    chunk: usize,
+}
+
+impl PartialEq for MergeEntry {
+    fn eq(&self, other: &MergeEntry) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeEntry {}
+
+impl PartialOrd for MergeEntry {
+    fn partial_cmp(&self, other: &MergeEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MergeEntry {
+    fn cmp(&self, other: &MergeEntry) -> Ordering {
+        self.key
+            .cmp(&other.key)
+            .then(self.chunk.cmp(&other.chunk))
+    }
+}
+
+// Default output file for the sorted Remuneracao file: csv/201807_Remuneracao.csv -> csv/201807_RemuneracaoParsed.csv,
+// or csv/201807_Servidores.zip -> csv/201807_Servidores_RemuneracaoParsed.csv when it comes from an archive
+pub fn default_output_file(input_file: &CsvSource) -> String {
+    let input_file = match input_file {
+        CsvSource::File(file) => file.clone(),
+        CsvSource::ZipMember { archive, .. } => {
+            format!("{}_Remuneracao.csv", &archive[..archive.len() - ".zip".len()])
+        }
+    };
+
+    if let Some(stripped) = input_file.strip_suffix(".csv") {
+        format!("{}Parsed.csv", stripped)
+    } else {
+        format!("{}Parsed.csv", input_file)
+    }
+}
+
+fn chunk_file(chunk: usize) -> PathBuf {
+    env::temp_dir().join(format!("fwb_sort_{}_{}.csv", process::id(), chunk))
+}
+
+// The sorted chunks in the temporary directory, which are removed when the sort ends, even when
+// it fails in the middle
+struct SortChunks {
+    paths: Vec<PathBuf>,
+}
+
+impl Drop for SortChunks {
+    fn drop(&mut self) {
+        for path in self.paths.iter() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+fn new_writer(path: &PathBuf) -> Result<Writer<File>, Box<dyn Error>> {
+    Ok(WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(false)
+        .from_path(path)?)
+}
+
+fn flush_chunk(
+    lines: &mut Vec<(SortKey, ByteRecord)>,
+    chunks: &mut SortChunks,
+) -> Result<(), Box<dyn Error>> {
+    lines.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // Kept before it's written, so a chunk left in the middle is removed too
+    let path = chunk_file(chunks.paths.len());
+    chunks.paths.push(path.clone());
+    let mut writer = new_writer(&path)?;
+    for (_, record) in lines.drain(..) {
+        writer.write_byte_record(&record)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Sorts the raw ';'-delimited Remuneracao file downloaded from the Portal da Transparencia by
+// name and ID, writing a ','-delimited UTF-8 file (header included) which can be fed to
+// parser::generate_database_files. The sort is done in chunks of SORT_CHUNK_SIZE lines which are
+// merged afterwards, so the whole file never needs to fit in memory. The Cadastro rows are found
+// by ID in a hash table, so the join doesn't need the sort, but the records get their entries in
+// the order of this file, and the searches list them by entry, so they come sorted by name, as
+// with the old script. The lines which can't be loaded go to the rejects
+pub fn sort_salary_file(
+    input_file: &CsvSource,
+    output_file: &str,
+    input_encoding: Encoding,
+    aliases: &Aliases,
+    category: Category,
+    rejects: &mut Rejects,
+) -> Result<(), Box<dyn Error>> {
+    let mut csv_reader = ReaderBuilder::new()
+        .delimiter(b';')
+        .flexible(true)
+        .from_reader(encoding::open(input_file, input_encoding)?);
+    let headers = csv_reader.headers()?.clone();
+    // Every column is checked here, so a wrong layout is noticed before the whole file is sorted
+    let column_map = ColumnMap::from_headers(
+        &headers,
+        &SALARY_COLUMNS,
+        category.required_salary_columns(),
+        aliases,
+        &input_file.name(),
+    )?;
+    let key_columns = KeyColumns {
+        name: column_map.index(Column::Nome),
+        id: column_map.index(Column::Id),
+    };
+
+    let input_file_name = input_file.name();
+    let columns_count = headers.len();
+    let mut chunks = SortChunks { paths: Vec::new() };
+    let mut lines: Vec<(SortKey, ByteRecord)> = Vec::with_capacity(SORT_CHUNK_SIZE);
+
+    for record in csv_reader.records() {
+        let record = match parser::check_row(record, columns_count, &input_file_name, rejects)? {
+            Some(record) => record,
+            None => continue,
+        };
+
+        // Lines without a numeric ID are the footer/garbage lines the Python script used to cut off
+        match SortKey::from_record(record.as_byte_record(), key_columns) {
+            Some(key) => lines.push((key, record.into_byte_record())),
+            None => {
+                rejects.add(
+                    &input_file_name,
+                    parser::line(&record),
+                    Reason::InvalidId,
+                    &record[key_columns.id],
+                    Some(&record),
+                )?;
+                continue;
+            }
+        }
+
+        if lines.len() >= SORT_CHUNK_SIZE {
+            flush_chunk(&mut lines, &mut chunks)?;
+            print!(".");
+            io::stdout().flush()?;
+        }
+    }
+    if !lines.is_empty() {
+        flush_chunk(&mut lines, &mut chunks)?;
+    }
+
+    // K-way merge of the sorted chunks
+    let mut readers = Vec::with_capacity(chunks.paths.len());
+    for path in chunks.paths.iter() {
+        readers.push(
+            ReaderBuilder::new()
+                .delimiter(b',')
+                .has_headers(false)
+                .from_path(path)?
+                .into_byte_records(),
+        );
+    }
+
+    let mut heap: BinaryHeap<Reverse<MergeEntry>> = BinaryHeap::new();
+    for (chunk, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = reader.next() {
+            let record = record?;
+            if let Some(key) = SortKey::from_record(&record, key_columns) {
+                heap.push(Reverse(MergeEntry { key, record, chunk }));
+            }
+        }
+    }
+
+    let mut writer = new_writer(&PathBuf::from(output_file))?;
+    writer.write_record(&headers)?;
+    while let Some(Reverse(entry)) = heap.pop() {
+        writer.write_byte_record(&entry.record)?;
+
+        if let Some(record) = readers[entry.chunk].next() {
+            let record = record?;
+            if let Some(key) = SortKey::from_record(&record, key_columns) {
+                heap.push(Reverse(MergeEntry {
+                    key,
+                    record,
+                    chunk: entry.chunk,
+                }));
+            }
+        }
+    }
+    writer.flush()?;
+
+    // The chunks are not needed anymore, and are removed when they are dropped
+    drop(readers);
+    drop(chunks);
+
+    Ok(())
+}
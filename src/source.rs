@@ -0,0 +1,186 @@
+use crc32fast::Hasher;
+use flate2::read::DeflateDecoder;
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+use zip::{CompressionMethod, ZipArchive};
+
+// A CSV file given to the program, which may be one of the members of the zip archive downloaded
+// from the Portal (e.g. 201807_Servidores.zip), read straight from it without being extracted
+#[derive(Clone, Debug)]
+pub enum CsvSource {
+    File(String),
+    ZipMember { archive: String, member: String },
+}
+
+// Checks the CRC of a zip member once it is read until the end
+struct CrcReader<R: Read> {
+    inner: R,
+    hasher: Hasher,
+    expected: u32,
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        if read > 0 {
+            self.hasher.update(&buffer[..read]);
+        } else if !buffer.is_empty() && self.hasher.clone().finalize() != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The zip member is corrupted (wrong CRC)",
+            ));
+        }
+
+        Ok(read)
+    }
+}
+
+impl CsvSource {
+    pub fn is_zip(file: &str) -> bool {
+        file.to_lowercase().ends_with(".zip")
+    }
+
+    // Finds the Remuneracao and Cadastro members of a Portal archive, which also has the
+    // Observacoes and Afastamentos files, not used here
+    pub fn from_zip(archive: &str) -> Result<(CsvSource, CsvSource), Box<dyn Error>> {
+        let zip = ZipArchive::new(File::open(archive)?)?;
+        let members: Vec<String> = zip.file_names().map(str::to_string).collect();
+
+        let find = |pattern: &str| -> Result<CsvSource, Box<dyn Error>> {
+            match members.iter().find(|member| {
+                let member = member.to_lowercase();
+                member.ends_with(".csv") && member.contains(pattern)
+            }) {
+                Some(member) => Ok(CsvSource::ZipMember {
+                    archive: archive.to_string(),
+                    member: member.clone(),
+                }),
+                None => Err(From::from(format!(
+                    "The archive {} has no {} CSV file, its files are: {}",
+                    archive,
+                    pattern,
+                    members.join(", ")
+                ))),
+            }
+        };
+
+        Ok((find("remuneracao")?, find("cadastro")?))
+    }
+
+    // Name shown in the messages, which is also used to detect the category and the period
+    pub fn name(&self) -> String {
+        match self {
+            CsvSource::File(file) => file.clone(),
+            CsvSource::ZipMember { archive, member } => format!("{}:{}", archive, member),
+        }
+    }
+
+    pub fn open(&self) -> Result<Box<dyn Read>, Box<dyn Error>> {
+        match self {
+            CsvSource::File(file) => Ok(Box::new(File::open(file)?)),
+            CsvSource::ZipMember { archive, member } => open_member(archive, member),
+        }
+    }
+}
+
+impl fmt::Display for CsvSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// The zip crate only lends its readers while the archive is borrowed, so the member is found with
+// it and then decompressed from its own handle of the file
+fn open_member(archive: &str, member: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let (data_start, compressed_size, compression, crc32) = {
+        let mut zip = ZipArchive::new(File::open(archive)?)?;
+        let file = zip.by_name(member)?;
+        (
+            file.data_start(),
+            file.compressed_size(),
+            file.compression(),
+            file.crc32(),
+        )
+    };
+
+    let mut file = File::open(archive)?;
+    file.seek(SeekFrom::Start(data_start))?;
+    let data = file.take(compressed_size);
+
+    let reader: Box<dyn Read> = match compression {
+        CompressionMethod::Stored => Box::new(data),
+        CompressionMethod::Deflated => Box::new(DeflateDecoder::new(data)),
+        other => {
+            return Err(From::from(format!(
+                "The file {} of the archive {} uses an unsupported compression ({})",
+                member, archive, other
+            )))
+        }
+    };
+
+    Ok(Box::new(CrcReader {
+        inner: reader,
+        hasher: Hasher::new(),
+        expected: crc32,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, io::Write, process};
+    use zip::{write::FileOptions, ZipWriter};
+
+    fn read(source: &CsvSource) -> Result<String, Box<dyn Error>> {
+        let mut text = String::new();
+        source.open()?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn reads_the_pair_from_the_zip() {
+        let dir = env::temp_dir().join(format!("fwb_source_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("201807_Servidores.zip").to_string_lossy().into_owned();
+
+        let members = [
+            ("201807_Observacoes.csv", CompressionMethod::Deflated),
+            ("201807_Remuneracao.csv", CompressionMethod::Deflated),
+            ("201807_Cadastro.csv", CompressionMethod::Stored),
+        ];
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        for (member, compression) in members.iter() {
+            zip.start_file(*member, FileOptions::default().compression_method(*compression))
+                .unwrap();
+            zip.write_all(format!("{}\n", member).repeat(100).as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let (salary_file, info_file) = CsvSource::from_zip(&archive).unwrap();
+        assert_eq!(salary_file.name(), format!("{}:201807_Remuneracao.csv", archive));
+        assert_eq!(read(&salary_file).unwrap(), "201807_Remuneracao.csv\n".repeat(100));
+        assert_eq!(read(&info_file).unwrap(), "201807_Cadastro.csv\n".repeat(100));
+
+        // A changed byte of the stored member is found by its CRC
+        let mut bytes = fs::read(&archive).unwrap();
+        let position = bytes
+            .windows(20)
+            .position(|window| window == b"201807_Cadastro.csv\n")
+            .unwrap();
+        bytes[position] = b'X';
+        fs::write(&archive, bytes).unwrap();
+        assert!(read(&info_file).is_err());
+
+        let only_salary = dir.join("only_salary.zip").to_string_lossy().into_owned();
+        let mut zip = ZipWriter::new(File::create(&only_salary).unwrap());
+        zip.start_file("201807_Remuneracao.csv", FileOptions::default()).unwrap();
+        zip.finish().unwrap();
+        assert!(CsvSource::from_zip(&only_salary).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,14 +1,88 @@
+use atomic_file;
+use crc32fast::Hasher;
+use data_dir;
+use header::{ChecksumReader, FileKind, Header, HEADER_SIZE};
 use parser;
 use record;
-use std::collections::HashMap;
-use std::mem::transmute;
+use record_view::MappedDatabase;
+use std::collections::{hash_map::Entry, HashMap};
 use std::{
     error,
     fs::{self, File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     str,
 };
 
+// Field of the Record indexed by a trie. The memory trie keeps the entries inserted in the
+// database after the trie was generated from it
+pub struct IndexedField {
+    pub label: &'static str,
+    pub trie_file: &'static str,
+    pub memory_trie_file: &'static str,
+    pub record_index: usize,
+}
+
+impl IndexedField {
+    pub fn trie_path(&self) -> String {
+        data_dir::path(self.trie_file)
+    }
+
+    pub fn memory_trie_path(&self) -> String {
+        data_dir::path(self.memory_trie_file)
+    }
+}
+
+pub const NAME_INDEX: IndexedField = IndexedField {
+    label: "name",
+    trie_file: "name_trie.bin",
+    memory_trie_file: "name_memory_trie.bin",
+    record_index: record::NOME,
+};
+
+pub const ROLE_INDEX: IndexedField = IndexedField {
+    label: "role",
+    trie_file: "role_trie.bin",
+    memory_trie_file: "role_memory_trie.bin",
+    record_index: record::DESCRICAO_CARGO,
+};
+
+pub const AGENCY_INDEX: IndexedField = IndexedField {
+    label: "agency",
+    trie_file: "agency_trie.bin",
+    memory_trie_file: "agency_memory_trie.bin",
+    record_index: record::ORGAO_EXERCICIO,
+};
+
+pub const CATEGORY_INDEX: IndexedField = IndexedField {
+    label: "category",
+    trie_file: "category_trie.bin",
+    memory_trie_file: "category_memory_trie.bin",
+    record_index: record::CATEGORIA,
+};
+
+pub const PERIOD_INDEX: IndexedField = IndexedField {
+    label: "period",
+    trie_file: "period_trie.bin",
+    memory_trie_file: "period_memory_trie.bin",
+    record_index: record::PERIODO,
+};
+
+pub const INDEXED_FIELDS: [IndexedField; 5] = [
+    NAME_INDEX,
+    ROLE_INDEX,
+    AGENCY_INDEX,
+    CATEGORY_INDEX,
+    PERIOD_INDEX,
+];
+
+// Each node has the quantity of its values (in 4 bytes), the values (4 each), the quantity of its
+// children (4) and the children. Each child has its character (its code point, in 4 bytes), its
+// position in the arena (4) and its address in the file (4). Up to the version 2 the character had
+// only 1 byte, so the ones after U+00FF were cut and mixed up with others, and up to the version 3
+// the quantities had 3 and 1 bytes, which a node with more than 255 children overflowed
+const LEN_SIZE: usize = 4;
+const CHILD_SIZE: usize = 12;
+
 #[derive(Debug, Default)]
 pub struct Node {
     chars: HashMap<char, u32>,
@@ -16,10 +90,11 @@ pub struct Node {
     address: u32,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Trie {
     nodes: Arena,
     root: u32,
+    pub header: Header,
 }
 
 #[derive(Debug, Default)]
@@ -34,62 +109,34 @@ impl Trie {
                 nodes: vec![Node::default()],
             },
             root: 0,
+            header: Header::new(FileKind::Trie),
+        }
+    }
+
+    pub fn from_nodes(nodes: Vec<Node>) -> Trie {
+        Trie {
+            nodes: Arena { nodes },
+            ..Trie::new()
         }
     }
 
     pub fn new_from_database(
         trie_file: String,
         record_index: usize,
-    ) -> Result<(), Box<error::Error>> {
+    ) -> Result<(), Box<dyn error::Error>> {
         let mut trie = Trie::new();
-        let mut f = File::open(parser::DATABASE_FILE)?;
-        let mut buffer: Vec<u8>;
-        let mut record = record::Record::default();
-        let mut record_counter = 0;
-
-        while !parser::exceeds_database_size(record_counter * record::DATA_ENTRY_SIZE as u64) {
-            //println!("{}", record_counter);
-            for (i, bytes) in record::RECORD_SIZES.iter().enumerate() {
-                buffer = vec![0; *bytes as usize];
-                f.read_exact(&mut buffer).unwrap();
-
-                let text: &str = str::from_utf8(&buffer).unwrap().trim_matches(char::from(0));
-
-                match i {
-                    0 => record.nome = text.as_bytes().to_vec(),
-                    1 => record.id = text.as_bytes().to_vec(),
-                    2 => record.cpf = text.as_bytes().to_vec(),
-                    3 => record.descricao_cargo = text.as_bytes().to_vec(),
-                    4 => record.orgao_exercicio = text.as_bytes().to_vec(),
-                    5 => record.remuneracao_basica_bruta_rs = text.as_bytes().to_vec(),
-                    6 => record.gratificacao_natalina_rs = text.as_bytes().to_vec(),
-                    7 => record.ferias_rs = text.as_bytes().to_vec(),
-                    8 => record.outras_remuneracoes_eventuais_rs = text.as_bytes().to_vec(),
-                    9 => record.irrf_rs = text.as_bytes().to_vec(),
-                    10 => record.pss_rgps_rs = text.as_bytes().to_vec(),
-                    11 => record.demais_deducoes_rs = text.as_bytes().to_vec(),
-                    12 => {
-                        record.remuneracao_apos_deducoes_obrigatorias_rs = text.as_bytes().to_vec()
-                    }
-                    13 => record.total_verbas_indenizatorias_rs = text.as_bytes().to_vec(),
-                    14 => record.data_inicio_afastamento = text.as_bytes().to_vec(),
-                    15 => record.data_termino_afastamento = text.as_bytes().to_vec(),
-                    16 => record.jornada_trabalho = text.as_bytes().to_vec(),
-                    17 => record.data_ingresso_cargo = text.as_bytes().to_vec(),
-                    18 => record.data_ingresso_orgao = text.as_bytes().to_vec(),
-                    _ => println!("Error!!"),
-                }
-            }
-
-            let value = record.get(record_index);
-            let split : Vec<&str> = value.split_whitespace().collect();
 
-            for piece in split {
-                trie.add(piece.to_string(), record_counter as u32 + 1); // Add each of the words
+        MappedDatabase::open(&parser::database_file())?.for_each(|entry, view| {
+            for piece in view.indexed_words(record_index) {
+                trie.add(piece.to_string(), entry); // Add each of the words
             }
+        })?;
 
-            record_counter += 1;
-        }
+        // The trie comes from the same files as the database
+        let database_header = parser::read_header(&parser::database_file())?;
+        trie.header.sources = database_header.sources;
+        trie.header.sources_count = database_header.sources_count;
+        trie.header.records_count = database_header.records_count;
 
         if let Err(err) = trie.save_to_file(&trie_file) {
             println!("Error saving the trie to a file: {}", err);
@@ -98,66 +145,43 @@ impl Trie {
         Ok(())
     }
 
-    pub fn new_from_file(trie_file: String) -> Result<Trie, Box<error::Error>> {
-        let mut trie = Trie {
-            nodes: Arena { nodes: Vec::new() },
-            root: 0,
-        };
-
-        let mut f = File::open(&trie_file)?;
-        let metadata = fs::metadata(&trie_file)?.len();
-
-        while f.seek(SeekFrom::Current(0))? < metadata {
-            let mut node: Node = Node::default();
-
-            // 1st, we catch the values stored in it
-            let mut values_len = vec![0; 3];
-            f.read_exact(&mut values_len)?;
+    pub fn new_from_file(trie_file: String) -> Result<Trie, Box<dyn error::Error>> {
+        let mut reader = BufReader::new(File::open(&trie_file)?);
+        let header = Header::read(&mut reader, &trie_file, FileKind::Trie)?;
+        let mut reader = ChecksumReader::new(reader);
+        let nodes = read_nodes(&mut reader, header.version)?;
+        if reader.checksum() != header.checksum {
+            return Err(From::from(format!(
+                "The trie {} is corrupted (wrong checksum)",
+                trie_file
+            )));
+        }
 
-            let mut values =
-                vec![0; (values_len[0] as u32 + ((values_len[1] as u32) << 8) + ((values_len[2] as u32) << 16)) as usize * 4];
-            let mut node_values: Vec<u32> = Vec::new();
-            f.read_exact(&mut values)?;
+        Ok(Trie {
+            nodes: Arena { nodes },
+            root: 0,
+            header,
+        })
+    }
 
-            for x in 0..values.len() / 4 {
-                node_values.push(
-                    ((values[x * 4 + 0] as u32) << 0)
-                        + ((values[x * 4 + 1] as u32) << 8)
-                        + ((values[x * 4 + 2] as u32) << 16)
-                        + ((values[x * 4 + 3] as u32) << 24),
-                );
-            }
-            node.val = node_values;
-
-            // 2nd, we read the quantity of children
-            let mut children_len = vec![0; 1];
-            f.read_exact(&mut children_len)?;
-
-            // 3rd, we read the characters
-            for _ in 0..children_len[0] {
-                let mut mapped_char = vec![0; 1];
-                let mut mapped_arena_position = vec![0; 4];
-                let mut _mapped_address = vec![0; 4];
-
-                f.read_exact(&mut mapped_char)?;
-                f.read_exact(&mut mapped_arena_position)?;
-                f.read_exact(&mut _mapped_address)?;
-
-                node.chars.insert(
-                    mapped_char[0] as char,
-                    ((mapped_arena_position[0] as u32) << 0)
-                        + ((mapped_arena_position[1] as u32) << 8)
-                        + ((mapped_arena_position[2] as u32) << 16)
-                        + ((mapped_arena_position[3] as u32) << 24),
-                );
+    // Every word of the trie which has values, with them, in no particular order
+    pub fn words(&self) -> Vec<(String, Vec<u32>)> {
+        let mut words: Vec<(String, Vec<u32>)> = Vec::new();
+        let mut stack: Vec<(u32, String)> = vec![(self.root, String::new())];
+        while let Some((node, word)) = stack.pop() {
+            if let Some(node) = self.nodes.nodes.get(node as usize) {
+                if !node.val.is_empty() {
+                    words.push((word.clone(), node.val.clone()));
+                }
+                for (character, child) in node.chars.iter() {
+                    let mut child_word = word.clone();
+                    child_word.push(*character);
+                    stack.push((*child, child_word));
+                }
             }
-
-            trie.nodes.nodes.push(node);
         }
 
-        trie.save_to_file(&trie_file)?;
-
-        return Ok(trie);
+        words
     }
 
     pub fn add(&mut self, string: String, val: u32) {
@@ -166,9 +190,9 @@ impl Trie {
         for c in string.chars() {
             let len = self.nodes.nodes.len(); // Prevent extra borrowing
 
-            if !self.nodes.nodes[node as usize].chars.contains_key(&c) {
+            if let Entry::Vacant(e) = self.nodes.nodes[node as usize].chars.entry(c) {
                 // Se não contem aquela chave
-                self.nodes.nodes[node as usize].chars.insert(c, len as u32); // Não precisa diminuir 1, pois será aumentado o tamanho
+                e.insert(len as u32); // Não precisa diminuir 1, pois será aumentado o tamanho
 
                 self.nodes.nodes.push(Node::default());
                 node = (self.nodes.nodes.len() - 1) as u32;
@@ -179,39 +203,31 @@ impl Trie {
         self.nodes.nodes[node as usize].val.push(val);
     }
 
-    pub fn save_to_file(&mut self, filename: &str) -> Result<(), Box<error::Error>> {
-        let mut output_file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(filename)?;
+    pub fn save_to_file(&mut self, filename: &str) -> Result<(), Box<dyn error::Error>> {
+        let mut output_file = BufWriter::new(atomic_file::create(filename)?);
+        let mut hasher = Hasher::new();
+        output_file.write_all(&self.header.to_bytes())?;
 
-        // Fakes the initial file creation, calculating the byte address
-        let mut counter: u32 = 0;
+        // Fakes the initial file creation, calculating the byte address. The nodes come after
+        // the header
+        let mut counter: u32 = HEADER_SIZE as u32;
         for node in self.nodes.nodes.iter_mut() {
             node.address = counter; // We will write this node at this point, so we use the old value for the counter
 
-            //let mut parsed_node : Vec<u8> = Vec::new();
-
-            // parsed_node.push(self.nodes.nodes[node_index].val.len() as u8);
-            // parsed_node.push((self.nodes.nodes[node_index].val.len() >> 8) as u8)
-            counter += 3; // 3 bytes for the node value vector length (quantity of values)
-
-            // for val in self.nodes.nodes[node_index].val.iter() {
-            //     let bytes: [u8; 4] = unsafe { transmute(val.to_le()) };
-            //     parsed_node.append(&mut bytes.to_vec());
-            // }
-            counter += node.val.len() as u32 * 4; // 4 bytes for each entry in the node value vector length
-
-            // parsed_node.push(self.nodes.nodes[node_index].chars.len() as u8);
-            counter += 1; // 1 byte for the node characters vector length (quantity of children)
-
-            // for (key, value) in self.nodes.nodes[node_index].chars.iter() {
-            //     parsed_node.push(*key as u8);
-            //     let node_address: [u8; 4] = unsafe { transmute(self.nodes.nodes[*value as usize].address.to_le()) };
-            //     parsed_node.append(&mut node_address.to_vec());
-            // }
-            counter += node.chars.len() as u32 * 9; // 1 byte for the value of the char, 4 bytes for their arena position and 4 bytes for the address for each of the mapped chars
+            // The quantities of values and children, 4 bytes for each value, and 4 bytes for the
+            // code point of the char, 4 bytes for their arena position and 4 bytes for the address
+            // for each of the mapped chars. The addresses have 4 bytes, so a bigger file can't be
+            // written, and the quantities always fit in their 4 bytes
+            let node_size = (2 * LEN_SIZE + node.val.len() * 4 + node.chars.len() * CHILD_SIZE) as u64;
+            counter = match u64::from(counter).checked_add(node_size) {
+                Some(end) if end <= u64::from(u32::MAX) => end as u32,
+                _ => {
+                    return Err(From::from(format!(
+                        "The trie {} would have more than 4 GiB, which its addresses can't reach",
+                        filename
+                    )))
+                }
+            };
         }
 
         // Print the actual file, with the proper byte address
@@ -219,34 +235,39 @@ impl Trie {
             let mut parsed_node: Vec<u8> = Vec::new();
 
             // Append the length of the vector with the value of the node
-            parsed_node.push(self.nodes.nodes[node_index].val.len() as u8);
-            parsed_node.push((self.nodes.nodes[node_index].val.len() >> 8) as u8);
-            parsed_node.push((self.nodes.nodes[node_index].val.len() >> 16) as u8);
+            parsed_node.extend_from_slice(&(self.nodes.nodes[node_index].val.len() as u32).to_le_bytes());
 
             // Append the vector with the value of the node
             for val in self.nodes.nodes[node_index].val.iter() {
-                let bytes: [u8; 4] = unsafe { transmute(val.to_le()) };
+                let bytes: [u8; 4] = val.to_le_bytes();
                 parsed_node.append(&mut bytes.to_vec());
             }
 
             // Append the quantity of children
-            parsed_node.push(self.nodes.nodes[node_index].chars.len() as u8);
+            parsed_node.extend_from_slice(&(self.nodes.nodes[node_index].chars.len() as u32).to_le_bytes());
 
             // Append the children letter, their position in the arena and 4 bytes which hold their address in the disk in the future
             for (key, value) in self.nodes.nodes[node_index].chars.iter() {
-                parsed_node.push(*key as u8);
+                parsed_node.extend_from_slice(&(*key as u32).to_le_bytes());
 
-                let arena_index: [u8; 4] = unsafe { transmute(value.to_le()) };
+                let arena_index: [u8; 4] = value.to_le_bytes();
                 parsed_node.append(&mut arena_index.to_vec());
 
                 let node_address: [u8; 4] =
-                    unsafe { transmute(self.nodes.nodes[*value as usize].address.to_le()) };
+                    self.nodes.nodes[*value as usize].address.to_le_bytes();
                 parsed_node.append(&mut node_address.to_vec());
             }
 
-            output_file.write(&parsed_node)?;
+            hasher.update(&parsed_node);
+            output_file.write_all(&parsed_node)?;
         }
 
+        // Now that the nodes are known, the header gets their checksum
+        self.header.checksum = hasher.finalize();
+        output_file.seek(SeekFrom::Start(0))?;
+        output_file.write_all(&self.header.to_bytes())?;
+        atomic_file::commit(filename, output_file)?;
+
         Ok(())
     }
 
@@ -254,38 +275,35 @@ impl Trie {
         string: &str,
         filename: &str,
         prefix_search: bool,
-    ) -> Result<Option<Vec<u32>>, Box<error::Error>> {
+    ) -> Result<Option<Vec<u32>>, Box<dyn error::Error>> {
         let mut input_file = OpenOptions::new().read(true).open(filename)?;
+        // The root node is right after the header
+        Header::read(&mut input_file, filename, FileKind::Trie)?;
 
-        if string.len() > 0 {
+        if !string.is_empty() {
             for character in string.chars() {
                 // 1st, we jump the values stored in it
-                let mut values_len = vec![0; 3];
+                let mut values_len = [0; LEN_SIZE];
                 input_file.read_exact(&mut values_len)?;
-                input_file.read_exact(&mut vec![
-                    0;
-                    (values_len[0] as u32 + ((values_len[1] as u32) << 8) + ((values_len[2] as u32) << 16))
-                        as usize
-                        * 4 as usize
-                ])?;
+                input_file.read_exact(&mut vec![0; u32::from_le_bytes(values_len) as usize * 4])?;
 
                 // 2nd, we read the quantity of children
-                let mut children_len = vec![0; 1];
+                let mut children_len = [0; LEN_SIZE];
                 input_file.read_exact(&mut children_len)?;
 
                 // 3rd, we search for the place we should seek for in the file
                 let mut found = false;
-                for _ in 0..children_len[0] {
-                    let mut mapped_char = vec![0; 1];
+                for _ in 0..u32::from_le_bytes(children_len) {
+                    let mut mapped_char = [0; 4];
                     let mut _mapped_arena_position = vec![0; 4];
                     let mut mapped_address = vec![0; 4];
                     input_file.read_exact(&mut mapped_char)?;
                     input_file.read_exact(&mut _mapped_arena_position)?;
                     input_file.read_exact(&mut mapped_address)?;
 
-                    if mapped_char[0] as char == character {
+                    if u32::from_le_bytes(mapped_char) == character as u32 {
                         // Found the future address, need to parse it
-                        let offset: u32 = ((mapped_address[0] as u32) << 0)
+                        let offset: u32 = (mapped_address[0] as u32)
                             + ((mapped_address[1] as u32) << 8)
                             + ((mapped_address[2] as u32) << 16)
                             + ((mapped_address[3] as u32) << 24);
@@ -303,43 +321,37 @@ impl Trie {
             if prefix_search {
                 // I need to fetch all the nodes behind me
                 let mut queue: Vec<u32> =
-                    vec![input_file.seek(SeekFrom::Current(0)).unwrap() as u32];
+                    vec![input_file.stream_position().unwrap() as u32];
                 let mut parsed_values: Vec<u32> = Vec::new();
 
-                while queue.len() > 0 {
+                while !queue.is_empty() {
                     // Retira um nodo por vez da fila, e pega o seu valor
                     let offset: u32 = queue.remove(0);
 
                     input_file.seek(SeekFrom::Start(offset as u64)).unwrap();
 
                     // 1st, we retrieve the values and fill the parsed_values array
-                    let mut values_len = vec![0; 3];
+                    let mut values_len = [0; LEN_SIZE];
                     input_file.read_exact(&mut values_len)?;
 
-                    let mut values = vec![
-                        0;
-                        (values_len[0] as u32 + ((values_len[1] as u32) << 8) + ((values_len[2] as u32) << 16))
-                            as usize
-                            * 4 as usize
-                    ];
+                    let mut values = vec![0; u32::from_le_bytes(values_len) as usize * 4];
                     input_file.read_exact(&mut values)?;
 
                     for x in 0..values.len() / 4 {
-                        let parsed_value: u32 = ((values[(x as usize * 4 + 0) as usize] as u32)
-                            << 0)
-                            + ((values[(x as usize * 4 + 1) as usize] as u32) << 8)
-                            + ((values[(x as usize * 4 + 2) as usize] as u32) << 16)
-                            + ((values[(x as usize * 4 + 3) as usize] as u32) << 24);
+                        let parsed_value: u32 = (values[x * 4] as u32)
+                            + ((values[x * 4 + 1] as u32) << 8)
+                            + ((values[x * 4 + 2] as u32) << 16)
+                            + ((values[x * 4 + 3] as u32) << 24);
                         parsed_values.push(parsed_value);
                     }
 
                     // 2nd, we read the quantity of children
-                    let mut children_len = vec![0; 1];
+                    let mut children_len = [0; LEN_SIZE];
                     input_file.read_exact(&mut children_len)?;
 
                     // 3rd, we search for the places we should still seek for in the file
-                    for _ in 0..children_len[0] {
-                        let mut _mapped_char = vec![0; 1];
+                    for _ in 0..u32::from_le_bytes(children_len) {
+                        let mut _mapped_char = vec![0; 4];
                         let mut _mapped_arena_position = vec![0; 4];
                         let mut mapped_address = vec![0; 4];
                         input_file.read_exact(&mut _mapped_char)?;
@@ -347,7 +359,7 @@ impl Trie {
                         input_file.read_exact(&mut mapped_address)?;
 
                         // Found the future address, need to parse it
-                        let offset: u32 = ((mapped_address[0] as u32) << 0)
+                        let offset: u32 = (mapped_address[0] as u32)
                             + ((mapped_address[1] as u32) << 8)
                             + ((mapped_address[2] as u32) << 16)
                             + ((mapped_address[3] as u32) << 24);
@@ -359,23 +371,18 @@ impl Trie {
             } else {
                 // I only need to fetch myself
                 // Fetch the values and return it
-                let mut values_len = vec![0; 3];
+                let mut values_len = [0; LEN_SIZE];
                 input_file.read_exact(&mut values_len)?;
 
-                let mut values = vec![
-                    0;
-                    (values_len[0] as u32 + ((values_len[1] as u32) << 8) + ((values_len[2] as u32) << 16))
-                        as usize
-                        * 4 as usize
-                ];
+                let mut values = vec![0; u32::from_le_bytes(values_len) as usize * 4];
                 input_file.read_exact(&mut values)?;
 
                 let mut parsed_values: Vec<u32> = Vec::new();
                 for x in 0..values.len() / 4 {
-                    let parsed_value: u32 = ((values[(x as usize * 4 + 0) as usize] as u32) << 0)
-                        + ((values[(x as usize * 4 + 1) as usize] as u32) << 8)
-                        + ((values[(x as usize * 4 + 2) as usize] as u32) << 16)
-                        + ((values[(x as usize * 4 + 3) as usize] as u32) << 24);
+                    let parsed_value: u32 = (values[x * 4] as u32)
+                        + ((values[x * 4 + 1] as u32) << 8)
+                        + ((values[x * 4 + 2] as u32) << 16)
+                        + ((values[x * 4 + 3] as u32) << 24);
                     parsed_values.push(parsed_value);
                 }
 
@@ -386,3 +393,188 @@ impl Trie {
         Ok(None)
     }
 }
+
+// The values of every node of a trie file, after checking its checksum, that its nodes aren't cut
+// and that the address of each child is the start of the node it should be
+pub fn check_file(trie_file: &str) -> Result<(Header, Vec<u32>), Box<dyn error::Error>> {
+    let bytes = fs::read(trie_file)?;
+    let header = Header::read(&mut &bytes[..], trie_file, FileKind::Trie)?;
+    if bytes.len() <= HEADER_SIZE as usize {
+        return Err(From::from("it doesn't have the root node"));
+    }
+
+    let cut = || "it ends in the middle of a node".to_string();
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    // The position of each node, with its place in the arena, and the children pointing to them
+    let mut nodes: HashMap<u32, u32> = HashMap::new();
+    let mut children: Vec<(u32, u32)> = Vec::new();
+    let mut values: Vec<u32> = Vec::new();
+    let mut position = HEADER_SIZE as usize;
+    while position < bytes.len() {
+        nodes.insert(position as u32, nodes.len() as u32);
+
+        let values_len = read_u32(bytes.get(position..position + LEN_SIZE).ok_or_else(cut)?) as usize;
+        position += LEN_SIZE;
+        let node_values = bytes.get(position..position + values_len * 4).ok_or_else(cut)?;
+        values.extend(node_values.chunks_exact(4).map(read_u32));
+        position += values_len * 4;
+
+        let children_len = read_u32(bytes.get(position..position + LEN_SIZE).ok_or_else(cut)?) as usize;
+        position += LEN_SIZE;
+        let node_children = bytes.get(position..position + children_len * CHILD_SIZE).ok_or_else(cut)?;
+        for child in node_children.chunks_exact(CHILD_SIZE) {
+            if char::from_u32(read_u32(&child[0..4])).is_none() {
+                return Err(From::from(format!(
+                    "a child has the code point {:#x}, which isn't a character",
+                    read_u32(&child[0..4])
+                )));
+            }
+            children.push((read_u32(&child[4..8]), read_u32(&child[8..12])));
+        }
+        position += children_len * CHILD_SIZE;
+    }
+
+    for (arena_index, address) in children {
+        if nodes.get(&address) != Some(&arena_index) {
+            return Err(From::from(format!(
+                "a child points to the address {}, which isn't the start of its node",
+                address
+            )));
+        }
+    }
+    // The damage which doesn't break the nodes
+    if crc32fast::hash(&bytes[HEADER_SIZE as usize..]) != header.checksum {
+        return Err(From::from("its checksum is wrong"));
+    }
+
+    Ok((header, values))
+}
+
+// Reads the nodes of a trie file, written with the version `version` of the format, from where
+// the reader is until the end of the file
+pub fn read_nodes<R: Read>(reader: &mut R, version: u16) -> Result<Vec<Node>, Box<dyn error::Error>> {
+    // The sizes of the quantity of values, of the quantity of children and of the character of
+    // each child, which were smaller in the older versions
+    let (values_len_size, children_len_size, char_size) = match version {
+        0..=2 => (3, 1, 1),
+        3 => (3, 1, 4),
+        _ => (LEN_SIZE, LEN_SIZE, 4),
+    };
+    let mut nodes: Vec<Node> = Vec::new();
+
+    loop {
+        let mut node: Node = Node::default();
+
+        // 1st, we catch the values stored in it
+        let mut values_len = [0; LEN_SIZE];
+        match reader.read_exact(&mut values_len[..values_len_size]) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(From::from(err)),
+        }
+
+        let mut values = vec![0; u32::from_le_bytes(values_len) as usize * 4];
+        let mut node_values: Vec<u32> = Vec::new();
+        reader.read_exact(&mut values)?;
+
+        for x in 0..values.len() / 4 {
+            node_values.push(
+                (values[x * 4] as u32)
+                    + ((values[x * 4 + 1] as u32) << 8)
+                    + ((values[x * 4 + 2] as u32) << 16)
+                    + ((values[x * 4 + 3] as u32) << 24),
+            );
+        }
+        node.val = node_values;
+
+        // 2nd, we read the quantity of children
+        let mut children_len = [0; LEN_SIZE];
+        reader.read_exact(&mut children_len[..children_len_size])?;
+
+        // 3rd, we read the characters
+        for _ in 0..u32::from_le_bytes(children_len) {
+            let mut mapped_char = [0; 4];
+            let mut mapped_arena_position = vec![0; 4];
+            let mut _mapped_address = vec![0; 4];
+
+            reader.read_exact(&mut mapped_char[..char_size])?;
+            reader.read_exact(&mut mapped_arena_position)?;
+            reader.read_exact(&mut _mapped_address)?;
+
+            let character = match char::from_u32(u32::from_le_bytes(mapped_char)) {
+                Some(character) => character,
+                None => return Err(From::from("The trie has a child which isn't a character")),
+            };
+            node.chars.insert(
+                character,
+                (mapped_arena_position[0] as u32)
+                    + ((mapped_arena_position[1] as u32) << 8)
+                    + ((mapped_arena_position[2] as u32) << 16)
+                    + ((mapped_arena_position[3] as u32) << 24),
+            );
+        }
+
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn test_dir(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("fwb_trie_{}_{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn round_trip_keeps_multi_byte_characters() {
+        let dir = test_dir("round_trip");
+        let trie_file = format!("{}/name_trie.bin", dir);
+        let words = ["JOÃO", "CONCEIÇÃO", "CONCEICAO", "ŒUVRE", "日本", "😀X"];
+
+        let mut trie = Trie::new();
+        for (i, word) in words.iter().enumerate() {
+            trie.add(word.to_string(), i as u32 + 1);
+        }
+        // More children than the 1 byte of the version 3 could count
+        for i in 0..300 {
+            trie.add(char::from_u32(0x100 + i).unwrap().to_string(), 1000 + i);
+        }
+        trie.save_to_file(&trie_file).unwrap();
+
+        // Loading doesn't change the file, and saving what was loaded gives the same trie
+        let saved = fs::read(&trie_file).unwrap();
+        let mut loaded = Trie::new_from_file(trie_file.clone()).unwrap();
+        assert_eq!(fs::read(&trie_file).unwrap(), saved);
+        let copy_file = format!("{}/copy_trie.bin", dir);
+        loaded.save_to_file(&copy_file).unwrap();
+
+        let loaded_words = loaded.words();
+        assert_eq!(loaded_words.len(), words.len() + 300);
+        assert!(loaded_words.contains(&("CONCEIÇÃO".to_string(), vec![2])));
+        assert!(loaded_words.contains(&("😀X".to_string(), vec![6])));
+
+        for file in [&trie_file, &copy_file].iter() {
+            let (_, values) = check_file(file).unwrap();
+            assert_eq!(values.len(), words.len() + 300);
+            for (i, word) in words.iter().enumerate() {
+                assert_eq!(Trie::at_from_file(word, file, false).unwrap(), Some(vec![i as u32 + 1]));
+            }
+            assert_eq!(Trie::at_from_file("ĭ", file, false).unwrap(), Some(vec![1045]));
+            // The 1 byte characters of the version 2 mixed up Ã (U+00C3) and Ń (U+0143)
+            assert_eq!(Trie::at_from_file("JOŃO", file, false).unwrap(), None);
+
+            let mut found = Trie::at_from_file("CONCEI", file, true).unwrap().unwrap();
+            found.sort();
+            assert_eq!(found, vec![2, 3]);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use parser;
+use std::fmt;
+
+// The links are saved in a single field of the Record, with these separators between the links
+// and between the fields of each link, as they can't appear in the Portal files
+pub const VINCULO_SEPARATOR: char = '\u{1E}';
+pub const VINCULO_FIELD_SEPARATOR: char = '\u{1F}';
+
+// One of the job links (vínculos) of a worker, from one of the rows of the Cadastro file.
+// A worker may have a role and a function (função comissionada) in different places
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vinculo {
+    pub cargo: String,
+    pub funcao: String,
+    pub uorg: String,
+    pub orgao: String,
+}
+
+// The Portal fills the empty fields with "-" or "Sem informação"
+fn clean(text: &str) -> String {
+    let text = text.trim();
+    if text == "-" || parser::is_sem_informacao(text) {
+        String::new()
+    } else {
+        text.to_string()
+    }
+}
+
+impl Vinculo {
+    pub fn new(cargo: &str, funcao: &str, uorg: &str, orgao: &str) -> Vinculo {
+        Vinculo {
+            cargo: clean(cargo),
+            funcao: clean(funcao),
+            uorg: clean(uorg),
+            orgao: clean(orgao),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cargo.is_empty() && self.funcao.is_empty() && self.uorg.is_empty() && self.orgao.is_empty()
+    }
+
+    fn from_text(text: &str) -> Vinculo {
+        let mut fields = text.split(VINCULO_FIELD_SEPARATOR).map(str::to_string);
+        Vinculo {
+            cargo: fields.next().unwrap_or_default(),
+            funcao: fields.next().unwrap_or_default(),
+            uorg: fields.next().unwrap_or_default(),
+            orgao: fields.next().unwrap_or_default(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        [
+            self.cargo.as_str(),
+            self.funcao.as_str(),
+            self.uorg.as_str(),
+            self.orgao.as_str(),
+        ].join(&VINCULO_FIELD_SEPARATOR.to_string())
+    }
+}
+
+impl fmt::Display for Vinculo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut description: Vec<&str> = Vec::new();
+        for field in [&self.cargo, &self.funcao, &self.uorg, &self.orgao].iter() {
+            if !field.is_empty() {
+                description.push(field);
+            }
+        }
+
+        write!(f, "{}", description.join(" - "))
+    }
+}
+
+pub fn from_text(text: &str) -> Vec<Vinculo> {
+    text.split(VINCULO_SEPARATOR)
+        .filter(|link| !link.is_empty())
+        .map(Vinculo::from_text)
+        .collect()
+}
+
+pub fn to_text(vinculos: &[Vinculo]) -> String {
+    vinculos
+        .iter()
+        .map(Vinculo::to_text)
+        .collect::<Vec<String>>()
+        .join(&VINCULO_SEPARATOR.to_string())
+}